@@ -2,13 +2,22 @@ extern crate proc_macro;
 
 use proc_macro2::{Ident, Literal, Span, TokenStream};
 use quote::{quote, ToTokens};
-use syn;
+use syn::{
+    parse::{Parse, ParseStream},
+    punctuated::Punctuated,
+    Token,
+};
 
 const CRATE_NAME: &str = "specs_dsl";
 
 #[proc_macro_attribute]
-pub fn data_item(_attrs: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    expand_data_item(item.into()).into()
+pub fn data_item(attrs: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_data_item(attrs.into(), item.into()).into()
+}
+
+#[proc_macro_attribute]
+pub fn resource_item(_attrs: proc_macro::TokenStream, item: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    expand_resource_item(item.into()).into()
 }
 
 #[proc_macro_attribute]
@@ -31,7 +40,8 @@ impl AttributeUtils for syn::Attribute {
     }
 }
 
-fn expand_data_item(input: TokenStream) -> TokenStream {
+fn expand_data_item(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let args = syn::parse2::<DataItemArgs>(attrs).expect("Failed parse attribute parameter");
     let mut item = parse_struct(input);
 
     let crate_name = crate_name();
@@ -41,20 +51,30 @@ fn expand_data_item(input: TokenStream) -> TokenStream {
         ext_generics,
     } = get_lifetimes(&item);
 
-    let item_tuple = syn::TypeTuple {
-        paren_token: Default::default(),
-        elems: item.fields.iter().map(|field| field.ty.clone()).collect(),
-    };
-    let item_init_from_tuple_fields: Vec<_> = item
+    let mut fields = extract_field_data(&mut item);
+    // Flattened items join as nested tuples which are converted into the nested items.
+    let item_tuple: Vec<_> = item
         .fields
         .iter()
-        .enumerate()
-        .map(|(i, syn::Field { ident, .. })| {
-            let i = Literal::usize_unsuffixed(i);
-            quote! { #ident: t.#i }
+        .zip(&fields)
+        .map(|(field, data)| {
+            let field_type = &field.ty;
+            match data.kind {
+                ItemFieldKind::Flatten => quote! { <#field_type as #crate_name::ItemTuple>::Tuple },
+                _ => quote! { #field_type },
+            }
         })
         .collect();
-    let fields = extract_field_data(&mut item);
+    let item_field_idents: Vec<_> = item.fields.iter().map(|field| field.ident.clone()).collect();
+    let removed = extract_removed_data(&mut item);
+    let mut item_tuples = vec![item_tuple.clone()];
+    if !removed.is_empty() {
+        // Removed components join as units after the item fields.
+        let mut removed_tuple = item_tuple;
+        removed_tuple.extend(removed.iter().map(|_| quote! { () }));
+        item_tuples.push(removed_tuple);
+    }
+    fields.extend(removed);
 
     let system_data_attr = extract_attr(&mut item.attrs, "system_data");
     let vis = &item.vis;
@@ -70,10 +90,17 @@ fn expand_data_item(input: TokenStream) -> TokenStream {
             view_mut_ret
         } = storages_main_views(&view_store_lifetime, &lifetime, &fields);
         let main_views_trait_name = syn::Ident::new(&format!("{}MainView", type_name), Span::call_site());
+        let main_views_doc = format!(
+            " The joins of the system data. If a call is ambiguous with another main view trait in scope, \
+             e.g. for flattened storages resolved through `ItemStorages`, call it qualified: \
+             `{}::view_mut(&mut data)`.",
+            main_views_trait_name
+        );
 
         quote! {
             #vis type #type_name<#lifetime> = #storages;
 
+            #[doc = #main_views_doc]
             pub trait #main_views_trait_name<'a> {
                 type ViewAllImmutable;
                 type ViewAllWithMut;
@@ -97,379 +124,2891 @@ fn expand_data_item(input: TokenStream) -> TokenStream {
         }
     });
 
+    let bundle_defs = args.bundle.map(|bundle| bundle_defs(&bundle, &item, &fields));
+    let serialize_defs = if args.serialize {
+        Some(serialize_defs(&item, &ext_lifetime, &fields))
+    } else {
+        None
+    };
+    let capture_defs = if args.capture {
+        Some(capture_defs(&item, &fields))
+    } else {
+        None
+    };
+    let hash_defs = if args.hash {
+        Some(hash_defs(&item, &fields))
+    } else {
+        None
+    };
+
     let (impl_data_view_generics, _, _) = ext_generics.split_for_impl();
     let storages_ref = storages(&ext_lifetime, Some(&item_lifetime), &fields);
     let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
     let item_type_name = &item.ident;
+    let static_args = item.generics.params.iter().map(|param| match param {
+        syn::GenericParam::Lifetime(_) => quote! { 'static },
+        syn::GenericParam::Type(param) => param.ident.to_token_stream(),
+        syn::GenericParam::Const(param) => param.ident.to_token_stream(),
+    });
+    let item_static_type = quote! { #item_type_name<#(#static_args),*> };
+    let mut storages_generics = item.generics.clone();
+    storages_generics.params = item
+        .generics
+        .params
+        .iter()
+        .map(|param| match param {
+            syn::GenericParam::Lifetime(_) => syn::GenericParam::Lifetime(syn::LifetimeDef::new(ext_lifetime.clone())),
+            param => param.clone(),
+        })
+        .collect();
+    let (impl_storages_generics, _, _) = storages_generics.split_for_impl();
+    // `'b: 'a` is implied by the trait, an explicit bound would prevent the impl from applying to all lifetimes.
+    let mut view_generics = ext_generics.clone();
+    for param in view_generics.params.iter_mut() {
+        if let syn::GenericParam::Lifetime(def) = param {
+            def.bounds.clear();
+        }
+    }
+    let (impl_view_generics, _, _) = view_generics.split_for_impl();
+    let storages_owned = storages(&ext_lifetime, None, &fields);
+    let storages_view = if fields.len() == 1 {
+        if let ItemFieldKind::Flatten = fields[0].kind {
+            let nested = item_view(&item_lifetime, &ext_lifetime, &fields[0]);
+            quote! { <#nested>::view(storages) }
+        } else if fields[0].kind.is_mut() {
+            quote! { storages }
+        } else {
+            quote! { &*storages }
+        }
+    } else {
+        let refs = fields.iter().zip(tuple_paths(fields.len())).map(|(field, i)| {
+            if let ItemFieldKind::Flatten = field.kind {
+                let nested = item_view(&item_lifetime, &ext_lifetime, field);
+                quote! { <#nested>::view(&mut storages.#i) }
+            } else if field.kind.is_mut() {
+                quote! { &mut storages.#i }
+            } else {
+                quote! { &storages.#i }
+            }
+        });
+        nested_tuple(refs.collect())
+    };
 
-    quote! {
-        #item
+    // A single joined storage yields the bare field type rather than a tuple.
+    let joined_type = |item_tuple: &Vec<TokenStream>| {
+        if item_tuple.len() == 1 {
+            item_tuple[0].clone()
+        } else {
+            nested_tuple(item_tuple.clone())
+        }
+    };
+    let joined_tuple = joined_type(item_tuples.last().unwrap());
+    let from_impls = item_tuples.iter().map(|item_tuple| {
+        let from_type = joined_type(item_tuple);
+        let item_init = if item_tuple.len() == 1 {
+            let init = match fields[0].kind {
+                ItemFieldKind::Flatten => quote! { t.into() },
+                _ => quote! { t },
+            };
+            quote! { #(#item_field_idents: #init),* }
+        } else {
+            let inits = item_field_idents
+                .iter()
+                .zip(&fields)
+                .zip(tuple_paths(item_tuple.len()))
+                .map(|((ident, field), i)| match field.kind {
+                    ItemFieldKind::Flatten => quote! { #ident: t.#i.into() },
+                    _ => quote! { #ident: t.#i },
+                });
+            quote! { #(#inits),* }
+        };
 
-        impl#impl_generics From<#item_tuple> for #item_type_name#type_generics #where_clause {
-            fn from(t: #item_tuple) -> Self {
-                Self {
-                    #(#item_init_from_tuple_fields),*
+        quote! {
+            impl#impl_generics From<#from_type> for #item_type_name#type_generics #where_clause {
+                fn from(t: #from_type) -> Self {
+                    Self {
+                        #item_init
+                    }
                 }
             }
         }
+    });
 
-        impl#impl_data_view_generics #crate_name::DataItem<#item_lifetime, #ext_lifetime> for #item_type_name#type_generics #where_clause {
-            type View = #storages_ref;
+    let lookup_methods = lookup_methods(&item_lifetime, &ext_lifetime, &fields, &item_field_idents);
+    let diagnose_method = diagnose_method(&fields);
+
+    // Flattened items track changes if any nested item does.
+    let tracks_changes = if fields
+        .iter()
+        .any(|field| field.filter.is_some() || matches!(field.kind, ItemFieldKind::Removed))
+    {
+        Some(quote! { true })
+    } else {
+        let nested: Vec<_> = fields
+            .iter()
+            .filter(|field| matches!(field.kind, ItemFieldKind::Flatten))
+            .map(|field| {
+                let static_type = static_type(&field.field_type);
+                quote! { <#static_type as #crate_name::ItemStorages<#ext_lifetime>>::TRACKS_CHANGES }
+            })
+            .collect();
+        if nested.is_empty() {
+            None
+        } else {
+            Some(quote! { #(#nested)||* })
         }
+    };
+    let tracks_changes = tracks_changes.map(|tracks_changes| quote! { const TRACKS_CHANGES: bool = #tracks_changes; });
 
-        #system_data_defs
-    }
-}
+    // Resources cannot be joined, so items with resource fields are consumed only through their system data.
+    let has_resources = fields
+        .iter()
+        .any(|field| matches!(field.kind, ItemFieldKind::Resource | ItemFieldKind::MutResource));
+    let item_view_impls = if has_resources {
+        None
+    } else {
+        Some(quote! {
+            impl#impl_storages_generics #crate_name::ItemStorages<#ext_lifetime> for #item_static_type #where_clause {
+                type Storages = #storages_owned;
 
-fn expand_data_view(_attrs: TokenStream, _input: TokenStream) -> TokenStream {
-    unimplemented!();
-}
+                #tracks_changes
+            }
 
-fn expand_system(attrs: TokenStream, input: TokenStream) -> TokenStream {
-    let system_data = syn::parse2::<syn::Ident>(attrs).expect("Failed parse attribute parameter");
-    let mut item = syn::parse2::<syn::ItemImpl>(input).expect("Failed parse system impl block");
+            impl#impl_view_generics #crate_name::ItemView<#item_lifetime, #ext_lifetime> for #item_static_type #where_clause {
+                type Item = #item_type_name#type_generics;
+                type View = #storages_ref;
 
-    let crate_name = crate_name();
-    let system_type = (*item.self_ty).clone();
-    let run_method = item
-        .items
-        .iter_mut()
-        .find_map(|item| match item {
-            syn::ImplItem::Method(method) => {
-                if extract_attr(&mut method.attrs, "run").is_some() {
-                    Some(method.sig.ident.clone())
-                } else {
-                    None
+                fn view(storages: &#item_lifetime mut Self::Storages) -> Self::View {
+                    #storages_view
                 }
             }
-            _ => None,
         })
-        .expect("Cannot find the run-annotated method");
+    };
 
     quote! {
         #item
 
-        impl<'a> #crate_name::specs::System<'a> for #system_type {
-            type SystemData = #system_data<'a>;
+        #(#from_impls)*
 
-            fn run(&mut self, data: Self::SystemData) {
-                self.#run_method(data);
-            }
+        impl#impl_generics #crate_name::ItemTuple for #item_type_name#type_generics #where_clause {
+            type Tuple = #joined_tuple;
         }
-    }
-}
 
-fn parse_struct(input: TokenStream) -> syn::ItemStruct {
-    let item = syn::parse2(input).expect("Failed parse input token stream");
-    match item {
-        syn::Item::Struct(item) => item,
-        _ => panic!("The data item must be a struct"),
-    }
-}
+        impl#impl_generics #item_type_name#type_generics #where_clause {
+            #lookup_methods
 
-struct Lifetimes {
-    item_lifetime: syn::Lifetime,
-    ext_lifetime: syn::Lifetime,
-    ext_generics: syn::Generics,
-}
+            #diagnose_method
+        }
 
-fn get_lifetimes(item: &syn::ItemStruct) -> Lifetimes {
-    let item_lifetime = match item
-        .generics
-        .params
-        .iter()
-        .next()
-        .expect("The data item must have at least one generic parameter with lifetime")
-    {
-        syn::GenericParam::Lifetime(def) => def.lifetime.clone(),
-        _ => panic!("The data item must have one lifetime parameter"),
-    };
+        impl#impl_data_view_generics #crate_name::DataItem<#item_lifetime, #ext_lifetime> for #item_type_name#type_generics #where_clause {
+            type View = #storages_ref;
+        }
 
-    let ext_lifetime = syn::Lifetime::new(&format!("'b{}", item_lifetime.ident), Span::call_site());
-    let mut ext_lifetime_def = syn::LifetimeDef::new(ext_lifetime.clone());
-    ext_lifetime_def.bounds.push(item_lifetime.clone());
+        #item_view_impls
 
-    let mut ext_params = vec![];
-    let mut is_lifetime_group_processed = false;
-    for param in &item.generics.params {
-        if let syn::GenericParam::Lifetime(_) = param {
-        } else {
-            ext_params.push(syn::GenericParam::Lifetime(ext_lifetime_def.clone()));
-            is_lifetime_group_processed = true;
-        }
-        ext_params.push(param.clone());
-    }
-    if !is_lifetime_group_processed {
-        ext_params.push(syn::GenericParam::Lifetime(ext_lifetime_def));
-    }
-    let mut ext_generics = item.generics.clone();
-    ext_generics.params = ext_params.into_iter().collect();
+        #system_data_defs
 
-    Lifetimes {
-        item_lifetime,
-        ext_lifetime,
-        ext_generics,
+        #bundle_defs
+
+        #serialize_defs
+        #capture_defs
+        #hash_defs
     }
 }
 
-fn get_attr<'a>(attrs: &'a [syn::Attribute], name: &str) -> Option<&'a syn::Attribute> {
-    attrs.iter().find(|attr| attr.is_name(name))
-}
+/// `get` and `get_mut` look up the item of a single entity in the item storages.
+/// Component storages check the entity generation, so items of dead entities are not found.
+fn lookup_methods(
+    item_lifetime: &syn::Lifetime,
+    store_lifetime: &syn::Lifetime,
+    fields: &[ItemFieldData],
+    idents: &[Option<Ident>],
+) -> TokenStream {
+    let crate_name = crate_name();
+    let storages_type = storages(store_lifetime, None, fields);
+    let lookup = |is_mut: bool| {
+        let mut guards = vec![];
+        let mut values = vec![];
+        for (field, i) in fields.iter().zip(tuple_paths(fields.len())) {
+            let storage = if fields.len() == 1 {
+                quote! { storages }
+            } else {
+                quote! { storages.#i }
+            };
+            if field.filter.is_some() || matches!(field.kind, ItemFieldKind::Removed) {
+                guards.push(quote! {
+                    if !#storage.mask().contains(entity.id()) {
+                        return None;
+                    }
+                });
+            }
+            let value = match field.kind {
+                ItemFieldKind::Flatten if is_mut => {
+                    let field_type = &field.field_type;
+                    let storage = if fields.len() == 1 {
+                        quote! { storages }
+                    } else {
+                        quote! { &mut #storage }
+                    };
+                    quote! { <#field_type>::get_mut(#storage, entity)? }
+                }
+                ItemFieldKind::Entity => quote! { Some(entity).filter(|entity| #storage.is_alive(*entity))? },
+                ItemFieldKind::Component => quote! { #storage.get(entity)? },
+                ItemFieldKind::MutComponent if is_mut => quote! { #storage.get_mut(entity)? },
+                ItemFieldKind::Resource => quote! { &*#storage },
+                ItemFieldKind::MutResource if is_mut => quote! { &mut *#storage },
+                ItemFieldKind::MutComponent | ItemFieldKind::MutResource | ItemFieldKind::Flatten => {
+                    unreachable!("Mutable fields are looked up only by get_mut")
+                }
+                ItemFieldKind::Removed => continue,
+            };
+            values.push(value);
+        }
 
-fn extract_attr(attrs: &mut Vec<syn::Attribute>, name: &str) -> Option<syn::Attribute> {
-    attrs
-        .iter()
-        .enumerate()
-        .find_map(|(i, attr)| if attr.is_name(name) { Some(i) } else { None })
-        .map(|idx| attrs.remove(idx))
-}
+        quote! {
+            #(#guards)*
+            Some(Self {
+                #(#idents: #values),*
+            })
+        }
+    };
 
-fn crate_name() -> Ident {
-    Ident::new(CRATE_NAME, Span::call_site())
-}
+    let get_mut_body = lookup(true);
+    let get_mut = quote! {
+        /// The item of the entity, or `None` if the entity is dead or doesn't match the item.
+        pub fn get_mut<#store_lifetime>(storages: &#item_lifetime mut #storages_type, entity: #crate_name::specs::Entity) -> Option<Self> {
+            #get_mut_body
+        }
+    };
+    if fields.iter().any(|field| field.kind.is_mut()) {
+        return get_mut;
+    }
 
-enum ItemFieldKind {
-    Entity,
-    Component,
-    Resource,
-    MutComponent,
-    MutResource,
+    let get_body = lookup(false);
+    quote! {
+        /// The item of the entity, or `None` if the entity is dead or doesn't match the item.
+        pub fn get<#store_lifetime>(storages: &#item_lifetime #storages_type, entity: #crate_name::specs::Entity) -> Option<Self> {
+            #get_body
+        }
+
+        #get_mut
+    }
 }
 
-impl ItemFieldKind {
-    fn is_mut(&self) -> bool {
-        match self {
-            ItemFieldKind::MutComponent | ItemFieldKind::MutResource => true,
-            _ => false,
+fn diagnose_method(fields: &[ItemFieldData]) -> TokenStream {
+    let crate_name = crate_name();
+    let checks = fields.iter().filter_map(|field| {
+        let field_type = &field.field_type;
+        match field.kind {
+            ItemFieldKind::Component | ItemFieldKind::MutComponent => {
+                Some(quote! { report.check_component::<#field_type>(world); })
+            }
+            ItemFieldKind::Resource | ItemFieldKind::MutResource => {
+                Some(quote! { report.check_resource::<#field_type>(world); })
+            }
+            ItemFieldKind::Flatten => Some(quote! { report.check_item(<#field_type>::diagnose(world, entity)); }),
+            ItemFieldKind::Entity | ItemFieldKind::Removed => None,
+        }
+    });
+
+    quote! {
+        /// Explains whether the entity matches the item, e.g. for debug tooling and test failures.
+        pub fn diagnose(world: &#crate_name::specs::World, entity: #crate_name::specs::Entity) -> #crate_name::MatchReport {
+            let mut report = #crate_name::MatchReport::new::<Self>(world, entity);
+            #(#checks)*
+            report
         }
     }
 }
 
-struct ItemFieldData {
-    kind: ItemFieldKind,
-    field_type: syn::Type,
-}
+/// The serde record of the item components and the `save` and `load` functions of the item entities.
+/// Entities are identified by save markers, so entity references inside components are remapped on load.
+fn serialize_defs(item: &syn::ItemStruct, store_lifetime: &syn::Lifetime, fields: &[ItemFieldData]) -> TokenStream {
+    if item.generics.type_params().next().is_some() || item.generics.const_params().next().is_some() {
+        panic!("Serialization is not supported for generic data items");
+    }
 
-fn extract_field_data(item: &mut syn::ItemStruct) -> Vec<ItemFieldData> {
-    item.fields
-        .iter_mut()
-        .map(|field| {
-            let (is_mut, field_type) = match &field.ty {
-                syn::Type::Reference(ref_type) => (ref_type.mutability.is_some(), (*ref_type.elem).clone()),
-                _ => (false, field.ty.clone()),
-            };
-            let is_entity = extract_attr(&mut field.attrs, "entity").is_some()
-                || format!("{}", field.ty.to_token_stream()).as_str() == "Entity";
-            let is_resource = extract_attr(&mut field.attrs, "resource").is_some();
-            let is_component = extract_attr(&mut field.attrs, "component").is_some() || (!is_entity && !is_resource);
+    let crate_name = crate_name();
+    let components =
+        plain_components(item, fields).expect("Serializable data items support only component and entity fields");
+    if components.is_empty() {
+        panic!("The serializable data item must contain at least one component");
+    }
 
-            let kind = if is_component {
-                if is_mut {
-                    ItemFieldKind::MutComponent
-                } else {
-                    ItemFieldKind::Component
-                }
-            } else if is_resource {
-                if is_mut {
-                    ItemFieldKind::MutResource
+    let vis = &item.vis;
+    let item_type_name = &item.ident;
+    let record = Ident::new(&format!("{}Record", item_type_name), Span::call_site());
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+    let serde_crate = format!("{}::serde", CRATE_NAME);
+    let saved_type = |ty: &syn::Type| {
+        quote! { <#ty as #crate_name::specs::saveload::ConvertSaveload<#crate_name::SaveMarker>>::Data }
+    };
+    let record_fields = components.iter().map(|(vis, ident, ty)| {
+        let saved_type = saved_type(ty);
+        quote! { #vis #ident: #saved_type }
+    });
+    let idents: Vec<_> = components.iter().map(|(_, ident, _)| ident).collect();
+    let types: Vec<_> = components.iter().map(|(_, _, ty)| ty).collect();
+    let storages_type = storages(store_lifetime, None, fields);
+    let mut joined = vec![quote! { &*entities }];
+    joined.extend(
+        fields
+            .iter()
+            .zip(tuple_paths(fields.len()))
+            .filter(|(field, _)| !matches!(field.kind, ItemFieldKind::Entity))
+            .map(|(_, i)| {
+                if fields.len() == 1 {
+                    quote! { storages }
                 } else {
-                    ItemFieldKind::Resource
+                    quote! { &storages.#i }
                 }
-            } else if is_entity {
-                ItemFieldKind::Entity
-            } else {
-                unreachable!("Unsupported item kind")
-            };
+            }),
+    );
+    let paths = tuple_paths(joined.len());
+    let entity_path = &paths[0];
+    let component_paths = &paths[1..];
+    let joined = nested_tuple(joined);
+    let storages: Vec<_> = idents
+        .iter()
+        .map(|ident| Ident::new(&format!("{}_storage", ident), Span::call_site()))
+        .collect();
+    let world_ext = quote! { #crate_name::specs::WorldExt };
+    let marker_allocator = quote! { #crate_name::specs::saveload::MarkerAllocator };
+    let convert_saveload = quote! { #crate_name::specs::saveload::ConvertSaveload };
 
-            ItemFieldData {
-                kind,
-                field_type,
+    quote! {
+        /// The saved components of an entity, see `save` and `load` of the data item.
+        #[derive(#crate_name::serde::Serialize, #crate_name::serde::Deserialize)]
+        #[serde(crate = #serde_crate)]
+        #vis struct #record {
+            pub marker: #crate_name::SaveMarker,
+            #(#record_fields),*
+        }
+
+        impl#impl_generics #item_type_name#type_generics #where_clause {
+            /// Saves the entities matching the item, marking them and the entities their components refer to.
+            pub fn save<#store_lifetime>(
+                storages: &#storages_type,
+                (entities, markers, allocator): &mut #crate_name::SaveMarkers<#store_lifetime>,
+            ) -> Result<#crate_name::Snapshot<#record>, #crate_name::SnapshotError> {
+                let mut items = vec![];
+                for joined in #crate_name::specs::Join::join(#joined) {
+                    let mut ids =
+                        |entity| #marker_allocator::mark(&mut **allocator, entity, markers).map(|(marker, _)| *marker);
+                    items.push(#record {
+                        marker: ids(joined.#entity_path).expect("Joined entities are alive"),
+                        #(#idents: #convert_saveload::convert_into(joined.#component_paths, &mut ids)
+                            .map_err(#crate_name::SnapshotError::convert)?),*
+                    });
+                }
+                Ok(#crate_name::Snapshot { items })
             }
-        })
-        .collect()
-}
 
-fn storages(
-    store_lifetime: &syn::Lifetime,
-    refs_lifetime: Option<&syn::Lifetime>,
-    fields: &[ItemFieldData],
-) -> TokenStream {
-    let crate_name = crate_name();
-    let storages: Vec<_> = fields
-        .iter()
-        .map(|field| {
-            let ref_part = refs_lifetime
-                .map(|lifetime| quote! { &#lifetime })
-                .unwrap_or_else(|| quote! {});
-            let ref_mut_part = refs_lifetime
-                .map(|lifetime| quote! { &#lifetime mut })
-                .unwrap_or_else(|| quote! {});
-            let field_type = &field.field_type;
+            /// Loads the saved entities, reusing the entities which have the same markers in the world.
+            pub fn load(
+                world: &mut #crate_name::specs::World,
+                snapshot: #crate_name::Snapshot<#record>,
+            ) -> Result<Vec<#crate_name::specs::Entity>, #crate_name::SnapshotError> {
+                #crate_name::register_save_markers(world);
+                #(#world_ext::register::<#types>(world);)*
+                let entities = #world_ext::entities(world);
+                let mut markers = #world_ext::write_storage::<#crate_name::SaveMarker>(world);
+                let mut allocator = #world_ext::write_resource::<#crate_name::SaveMarkerAllocator>(world);
+                #(let mut #storages = #world_ext::write_storage::<#types>(world);)*
 
-            match field.kind {
-                ItemFieldKind::Entity => quote! { #ref_part #crate_name::specs::Entities<#store_lifetime> },
-                ItemFieldKind::Component => {
-                    quote! { #ref_part #crate_name::specs::ReadStorage<#store_lifetime, #field_type> }
-                }
-                ItemFieldKind::Resource => quote! { #ref_part #crate_name::specs::Read<#store_lifetime, #field_type> },
-                ItemFieldKind::MutComponent => {
-                    quote! { #ref_mut_part #crate_name::specs::WriteStorage<#store_lifetime, #field_type> }
-                }
-                ItemFieldKind::MutResource => {
-                    quote! { #ref_mut_part #crate_name::specs::Write<#store_lifetime, #field_type> }
+                let mut loaded = Vec::with_capacity(snapshot.items.len());
+                for record in snapshot.items {
+                    let entity = #marker_allocator::retrieve_entity(&mut *allocator, record.marker, &mut markers, &entities);
+                    let mut ids =
+                        |marker| Some(#marker_allocator::retrieve_entity(&mut *allocator, marker, &mut markers, &entities));
+                    #(
+                        let #idents = #convert_saveload::convert_from(record.#idents, &mut ids)
+                            .map_err(#crate_name::SnapshotError::convert)?;
+                        #storages.insert(entity, #idents)?;
+                    )*
+                    loaded.push(entity);
                 }
+                Ok(loaded)
             }
-        })
-        .collect();
-
-    if storages.len() == 1 {
-        storages.into_iter().next().unwrap()
-    } else {
-        quote! { (#(#storages),*) }
+        }
     }
 }
 
-struct MainViews {
-    view_type: TokenStream,
-    view_ret: TokenStream,
-    view_mut_type: TokenStream,
-    view_mut_ret: TokenStream,
+/// The unfiltered component fields of the item, or `None` if the item has fields other than components and entities.
+fn plain_components<'a>(
+    item: &'a syn::ItemStruct,
+    fields: &'a [ItemFieldData],
+) -> Option<Vec<(&'a syn::Visibility, &'a Ident, &'a syn::Type)>> {
+    if fields.len() > item.fields.len() {
+        return None;
+    }
+    let mut components = vec![];
+    for (field, data) in item.fields.iter().zip(fields) {
+        match data.kind {
+            ItemFieldKind::Component | ItemFieldKind::MutComponent if data.filter.is_none() => {
+                let ident = field.ident.as_ref().expect("Data item fields must be named");
+                components.push((&field.vis, ident, &data.field_type));
+            }
+            ItemFieldKind::Entity => {}
+            _ => return None,
+        }
+    }
+    Some(components)
 }
 
-fn storages_main_views(
-    store_lifetime: &syn::Lifetime,
-    refs_lifetime: &syn::Lifetime,
-    fields: &[ItemFieldData],
-) -> MainViews {
+/// A cloned capture of the item components, which is compared with `Capture::diff`.
+fn capture_defs(item: &syn::ItemStruct, fields: &[ItemFieldData]) -> TokenStream {
+    if item.generics.type_params().next().is_some() || item.generics.const_params().next().is_some() {
+        panic!("Captures are not supported for generic data items");
+    }
+
     let crate_name = crate_name();
-    let mut view_indexes = vec![];
-    let view_storages: Vec<_> = fields
+    let components =
+        plain_components(item, fields).expect("Captured data items support only component and entity fields");
+    if components.is_empty() {
+        panic!("The captured data item must contain at least one component");
+    }
+
+    let vis = &item.vis;
+    let item_type_name = &item.ident;
+    let record = Ident::new(&format!("{}Capture", item_type_name), Span::call_site());
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+    let record_fields = components.iter().map(|(vis, ident, ty)| quote! { #vis #ident: #ty });
+    let idents: Vec<_> = components.iter().map(|(_, ident, _)| ident).collect();
+    let names: Vec<_> = idents.iter().map(|ident| ident.to_string()).collect();
+    let types: Vec<_> = components.iter().map(|(_, _, ty)| ty).collect();
+    let storages: Vec<_> = idents
         .iter()
-        .enumerate()
-        .filter_map(|(idx, field)| {
-            let field_type = &field.field_type;
+        .map(|ident| Ident::new(&format!("{}_storage", ident), Span::call_site()))
+        .collect();
+    let mut joined = vec![quote! { &entities }];
+    joined.extend(storages.iter().map(|storage| quote! { &#storage }));
+    let paths = tuple_paths(joined.len());
+    let entity_path = &paths[0];
+    let component_paths = &paths[1..];
+    let joined = nested_tuple(joined);
+    let world_ext = quote! { #crate_name::specs::WorldExt };
+
+    quote! {
+        /// The cloned components of an entity, see `capture` of the data item.
+        #[derive(Clone, Debug, PartialEq)]
+        #vis struct #record {
+            #(#record_fields),*
+        }
+
+        impl #crate_name::CaptureRecord for #record {
+            fn changed_fields(&self, other: &Self) -> Vec<&'static str> {
+                let mut fields = vec![];
+                #(
+                    if self.#idents != other.#idents {
+                        fields.push(#names);
+                    }
+                )*
+                fields
+            }
+        }
+
+        impl#impl_generics #item_type_name#type_generics #where_clause {
+            /// Clones the components of the entities matching the item.
+            pub fn capture(world: &#crate_name::specs::World) -> #crate_name::Capture<#record> {
+                let entities = #world_ext::entities(world);
+                #(let #storages = #world_ext::read_storage::<#types>(world);)*
+
+                #crate_name::Capture {
+                    items: #crate_name::specs::Join::join(#joined)
+                        .map(|joined| {
+                            let record = #record {
+                                #(#idents: Clone::clone(joined.#component_paths)),*
+                            };
+                            (joined.#entity_path, record)
+                        })
+                        .collect(),
+                }
+            }
+        }
+    }
+}
+
+/// Hashes the entities and components of the item, which is a state hook of the replay `Recorder` and `Replayer`.
+fn hash_defs(item: &syn::ItemStruct, fields: &[ItemFieldData]) -> TokenStream {
+    if item.generics.type_params().next().is_some() || item.generics.const_params().next().is_some() {
+        panic!("State hashes are not supported for generic data items");
+    }
+
+    let crate_name = crate_name();
+    let components =
+        plain_components(item, fields).expect("Hashed data items support only component and entity fields");
+    if components.is_empty() {
+        panic!("The hashed data item must contain at least one component");
+    }
+
+    let item_type_name = &item.ident;
+    let (impl_generics, type_generics, where_clause) = item.generics.split_for_impl();
+    let types: Vec<_> = components.iter().map(|(_, _, ty)| ty).collect();
+    let storages: Vec<_> = components
+        .iter()
+        .map(|(_, ident, _)| Ident::new(&format!("{}_storage", ident), Span::call_site()))
+        .collect();
+    let mut joined = vec![quote! { &entities }];
+    joined.extend(storages.iter().map(|storage| quote! { &#storage }));
+    let paths = tuple_paths(joined.len());
+    let joined = nested_tuple(joined);
+    let world_ext = quote! { #crate_name::specs::WorldExt };
+
+    quote! {
+        impl#impl_generics #item_type_name#type_generics #where_clause {
+            /// Hashes the entities matching the item and their components in the entity order.
+            pub fn hash_state(world: &#crate_name::specs::World, mut state: &mut dyn std::hash::Hasher) {
+                let entities = #world_ext::entities(world);
+                #(let #storages = #world_ext::read_storage::<#types>(world);)*
+
+                for joined in #crate_name::specs::Join::join(#joined) {
+                    #(std::hash::Hash::hash(&joined.#paths, &mut state);)*
+                }
+            }
+        }
+    }
+}
+
+/// The owned components of the item, which are spawned or inserted together.
+fn bundle_defs(bundle: &Ident, item: &syn::ItemStruct, fields: &[ItemFieldData]) -> TokenStream {
+    if item.generics.type_params().next().is_some() || item.generics.const_params().next().is_some() {
+        panic!("Bundles are not supported for generic data items");
+    }
+
+    if fields.iter().any(|field| matches!(field.kind, ItemFieldKind::Flatten)) {
+        panic!("Bundles are not supported for data items with flattened fields");
+    }
+
+    let crate_name = crate_name();
+    let vis = &item.vis;
+    let components: Vec<_> = item
+        .fields
+        .iter()
+        .zip(fields)
+        .filter(|(_, data)| matches!(data.kind, ItemFieldKind::Component | ItemFieldKind::MutComponent))
+        .map(|(field, data)| {
+            (
+                &field.vis,
+                field.ident.as_ref().expect("Data item fields must be named"),
+                &data.field_type,
+            )
+        })
+        .collect();
+    if components.is_empty() {
+        panic!("The bundle must contain at least one component");
+    }
+    let bundle_fields = components.iter().map(|(vis, ident, ty)| quote! { #vis #ident: #ty });
+    let idents: Vec<_> = components.iter().map(|(_, ident, _)| ident).collect();
+    let types: Vec<_> = components.iter().map(|(_, _, ty)| ty).collect();
+
+    quote! {
+        #vis struct #bundle {
+            #(#bundle_fields),*
+        }
+
+        impl #bundle {
+            /// Creates an entity with the bundle components, registering their storages if needed.
+            pub fn spawn(self, world: &mut #crate_name::specs::World) -> #crate_name::specs::Entity {
+                <Self as #crate_name::ComponentBundle>::register(world);
+                let entity = #crate_name::specs::WorldExt::entities(world).create();
+                #crate_name::ComponentBundle::insert_into(self, world, entity);
+                entity
+            }
+
+            /// Inserts the bundle components into the entity, e.g. from a system which writes the storages.
+            #[allow(clippy::too_many_arguments)]
+            pub fn insert_into(
+                self,
+                entity: #crate_name::specs::Entity,
+                #(#idents: &mut #crate_name::specs::WriteStorage<'_, #types>),*
+            ) -> Result<(), #crate_name::specs::error::Error> {
+                #(#idents.insert(entity, self.#idents)?;)*
+                Ok(())
+            }
+
+            /// Creates an entity whose components are inserted on the next `World::maintain`.
+            pub fn lazy_spawn(self, entities: &#crate_name::specs::world::EntitiesRes, lazy: &#crate_name::specs::LazyUpdate) -> #crate_name::specs::Entity {
+                let entity = entities.create();
+                self.lazy_insert(entity, lazy);
+                entity
+            }
+
+            /// Inserts the bundle components into the entity on the next `World::maintain`.
+            pub fn lazy_insert(self, entity: #crate_name::specs::Entity, lazy: &#crate_name::specs::LazyUpdate) {
+                #(lazy.insert(entity, self.#idents);)*
+            }
+        }
+
+        impl #crate_name::ComponentBundle for #bundle {
+            fn register(world: &mut #crate_name::specs::World) {
+                #(<#crate_name::specs::WriteStorage<#types> as #crate_name::specs::SystemData>::setup(world);)*
+            }
+
+            fn insert_into(self, world: &#crate_name::specs::World, entity: #crate_name::specs::Entity) {
+                #(#crate_name::specs::WorldExt::write_storage::<#types>(world)
+                    .insert(entity, self.#idents)
+                    .expect("Cannot insert a component into a dead entity");)*
+            }
+        }
+    }
+}
+
+fn expand_resource_item(input: TokenStream) -> TokenStream {
+    let mut item = parse_struct(input);
+
+    let Lifetimes {
+        item_lifetime,
+        ext_lifetime,
+        ext_generics,
+    } = get_lifetimes(&item);
+    let fields = extract_resource_data(&item);
+    let item_field_idents: Vec<_> = item.fields.iter().map(|field| field.ident.clone()).collect();
+
+    let system_data_attr = extract_attr(&mut item.attrs, "system_data");
+    let vis = &item.vis;
+    let system_data_defs = system_data_attr.map(|attr| {
+        let type_name = attr.parse_args::<syn::Ident>().expect("Cannot parse system data name");
+        let lifetime = syn::Lifetime::new("'a", Span::call_site());
+        let storages = storages(&lifetime, None, &fields);
+
+        quote! {
+            #vis type #type_name<#lifetime> = #storages;
+        }
+    });
+
+    let storages_type = storages(&ext_lifetime, None, &fields);
+    let refs = fields.iter().zip(tuple_paths(fields.len())).map(|(field, i)| {
+        let resource = if fields.len() == 1 {
+            quote! { data }
+        } else {
+            quote! { data.#i }
+        };
+        if field.kind.is_mut() {
+            quote! { &mut *#resource }
+        } else {
+            quote! { &*#resource }
+        }
+    });
+    let (impl_generics, _, _) = ext_generics.split_for_impl();
+    let (_, type_generics, where_clause) = item.generics.split_for_impl();
+    let item_type_name = &item.ident;
+
+    quote! {
+        #item
+
+        impl#impl_generics From<&#item_lifetime mut #storages_type> for #item_type_name#type_generics #where_clause {
+            fn from(data: &#item_lifetime mut #storages_type) -> Self {
+                Self {
+                    #(#item_field_idents: #refs),*
+                }
+            }
+        }
+
+        #system_data_defs
+    }
+}
+
+fn expand_data_view(_attrs: TokenStream, _input: TokenStream) -> TokenStream {
+    unimplemented!();
+}
+
+fn expand_system(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let args = syn::parse2::<SystemArgs>(attrs).expect("Failed parse attribute parameter");
+    let mut item = syn::parse2::<syn::ItemImpl>(input).expect("Failed parse system impl block");
+
+    let crate_name = crate_name();
+    let system_type = (*item.self_ty).clone();
+    let system_data = &args.system_data;
+    let run_method = extract_method_with_attr(&mut item, "run");
+    let hooks = extract_hooks(&mut item);
+    if run_method.is_none() && hooks.is_empty() {
+        panic!("Cannot find the run-annotated method");
+    }
+    let running_time_method = extract_method_with_attr(&mut item, "running_time");
+    let system_name = system_name(&system_type);
+    let thread_local = args.thread_local;
+    if thread_local && args.batch {
+        panic!("Batch systems cannot be thread-local");
+    }
+
+    let running_time = match (&args.cost, running_time_method) {
+        (Some(_), Some(_)) => panic!("The system cost and the running_time-annotated method cannot be used together"),
+        (Some(cost), None) => {
+            let variant = match cost.value().as_str() {
+                "very_short" => "VeryShort",
+                "short" => "Short",
+                "average" => "Average",
+                "long" => "Long",
+                "very_long" => "VeryLong",
+                _ => panic!("The system cost must be one of: very_short, short, average, long, very_long"),
+            };
+            let variant = Ident::new(variant, cost.span());
+            Some(quote! { #crate_name::specs::RunningTime::#variant })
+        }
+        (None, Some(method)) => Some(quote! { self.#method() }),
+        (None, None) => None,
+    };
+    let running_time = running_time.map(|running_time| {
+        if thread_local {
+            panic!("Thread-local systems do not support running time hints");
+        }
+        quote! {
+            fn running_time(&self) -> #crate_name::specs::RunningTime {
+                #running_time
+            }
+        }
+    });
+
+    // Errors of a run method returning `Result` are handled by the error policy, collecting them by default.
+    let is_fallible = match &run_method {
+        Some(run_method) if !args.batch => match returns_result(&item, run_method) {
+            Ok(is_fallible) => is_fallible,
+            Err(error) => return error.to_compile_error(),
+        },
+        _ => false,
+    };
+    if args.batch && args.on_error.is_some() {
+        panic!("Batch systems do not support error policies");
+    }
+    if !is_fallible && args.on_error.is_some() {
+        panic!("The error policy requires the run-annotated method to return a Result");
+    }
+    let policy = args
+        .on_error
+        .as_ref()
+        .map_or_else(|| "collect".to_string(), |policy| policy.value());
+    let on_error = if is_fallible {
+        Some(match policy.as_str() {
+            "collect" => quote! { errors.push(error); },
+            "log" => quote! { #crate_name::log::error!("{}", error); },
+            "panic" => quote! { panic!("{}", error); },
+            _ => panic!("The error policy must be one of: collect, log, panic"),
+        })
+    } else {
+        None
+    };
+    let collects_errors = is_fallible && policy == "collect";
+
+    // Per-system state lives in a hidden resource which is fetched together with the declared data.
+    let state_type = Ident::new(&format!("__{}DslState", system_name), Span::call_site());
+    let mut state_fields = vec![];
+    let mut guards = vec![];
+
+    if let Some(every) = args.every {
+        let every = Literal::u64_unsuffixed(every);
+        state_fields.push(quote! { pub tick: u64 });
+        guards.push(quote! {
+            let tick = state.tick;
+            state.tick = tick.wrapping_add(1);
+            if tick % #every != 0 {
+                return;
+            }
+        });
+    }
+    if let Some(run_if) = &args.run_if {
+        guards.push(quote! {
+            if !(#run_if)(&data) {
+                return;
+            }
+        });
+    }
+    if args.batch && !guards.is_empty() {
+        panic!("Batch systems do not support run conditions, return 0 from the run method instead");
+    }
+    if args.batch && !args.channels.is_empty() {
+        panic!("Batch systems do not support event channels");
+    }
+    if args.batch && !hooks.is_empty() {
+        panic!("Batch systems do not support component hooks");
+    }
+    if args.batch && args.catch_panics {
+        panic!("Batch systems do not catch panics, their inner systems can do it instead");
+    }
+    if args.batch && args.metrics {
+        panic!("Batch systems do not record metrics, their inner systems can do it instead");
+    }
+    if run_method.is_none() && !args.channels.is_empty() {
+        panic!("Event channels are passed to the run-annotated method, which cannot be found");
+    }
+
+    for (idx, channel) in args.channels.iter().enumerate() {
+        let event_type = channel.event_type.to_token_stream().to_string();
+        let is_duplicate = args.channels[..idx]
+            .iter()
+            .any(|other| other.event_type.to_token_stream().to_string() == event_type);
+        if is_duplicate {
+            panic!("The event channel of `{}` is declared more than once", event_type);
+        }
+    }
+
+    // Event channels are fetched after the declared data and passed to the run method in the declared order.
+    let mut channel_pats = vec![];
+    let mut event_reads = vec![];
+    let mut event_args = vec![];
+    let mut register_events = vec![];
+    for (idx, channel) in args.channels.iter().enumerate() {
+        let event_type = &channel.event_type;
+        let channel_ident = Ident::new(&format!("channel_{}", idx), Span::call_site());
+        match channel.kind {
+            ChannelKind::Emits => {
+                channel_pats.push(quote! { mut #channel_ident });
+                event_args.push(quote! { #crate_name::Emitter::new(&mut #channel_ident) });
+            }
+            ChannelKind::Reads => {
+                let reader_ident = Ident::new(&format!("reader_{}", idx), Span::call_site());
+                let events_ident = Ident::new(&format!("events_{}", idx), Span::call_site());
+                state_fields.push(quote! { pub #reader_ident: Option<#crate_name::specs::ReaderId<#event_type>> });
+                channel_pats.push(quote! { #channel_ident });
+                event_reads.push(quote! {
+                    let #events_ident = #channel_ident.read(
+                        state.#reader_ident.as_mut().expect("The event reader is not registered, the system must be set up first")
+                    );
+                });
+                event_args.push(quote! { #events_ident });
+                register_events.push(quote! {
+                    world.fetch_mut::<#state_type>().#reader_ident = Some(
+                        world.fetch_mut::<#crate_name::specs::shrev::EventChannel<#event_type>>().register_reader()
+                    );
+                });
+            }
+        }
+    }
+
+    // Component hooks are fetched after the event channels and called before the run method.
+    let mut hook_pats = vec![];
+    let mut hook_calls = vec![];
+    let mut collect_hooks = vec![];
+    let mut register_hooks = vec![];
+    let hook_type = |lifetime: &TokenStream, hook: &HookMethod| {
+        let component = &hook.component;
+        let filter = match hook.kind {
+            HookKind::Insert => quote! { #crate_name::Inserted },
+            HookKind::Remove => quote! { #crate_name::Removal },
+        };
+        quote! { #crate_name::Hook<#lifetime, #component, #filter> }
+    };
+    if !hooks.is_empty() && args.changes.is_none() {
+        panic!(
+            "Component hooks keep their change readers in the system, declare the tracker field with `changes = field`"
+        );
+    }
+    let tracker = &args.changes;
+    for (idx, hook) in hooks.iter().enumerate() {
+        let hook_ident = Ident::new(&format!("hook_{}", idx), Span::call_site());
+        let hook_type = hook_type(&quote! { '_ }, hook);
+        let method = &hook.method;
+
+        hook_pats.push(quote! { mut #hook_ident });
+        let mut hook_call = quote! { #crate_name::__item_event!(#system_name, entity); };
+        hook_call.extend(if args.catch_panics {
+            quote! { #crate_name::with_entity_context(entity, || self.#method(entity, &mut data)); }
+        } else {
+            quote! { self.#method(entity, &mut data); }
+        });
+        if args.metrics {
+            hook_call.extend(quote! { items.add(1); });
+        }
+        collect_hooks.push(quote! {
+            #crate_name::TrackedData::collect_changes(&mut #hook_ident, &mut readers);
+        });
+        hook_calls.push(quote! {
+            for entity in #hook_ident.entities() {
+                #hook_call
+            }
+        });
+        register_hooks.push(quote! {
+            <#hook_type>::check_access::<#system_data<'_>>(::std::any::type_name::<Self>());
+            <#hook_type as #crate_name::TrackedData>::register_readers(world, &mut self.#tracker);
+        });
+    }
+
+    let state_def = if state_fields.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            #[doc(hidden)]
+            #[derive(Default)]
+            pub struct #state_type {
+                #(#state_fields),*
+            }
+        })
+    };
+    // The data is borrowed mutably to collect its changes and to pass it to the hooks.
+    let mut data_pats = vec![if args.changes.is_some() {
+        quote! { mut data }
+    } else {
+        quote! { data }
+    }];
+    if state_def.is_some() {
+        data_pats.push(quote! { mut state });
+    }
+    data_pats.extend(channel_pats);
+    data_pats.extend(hook_pats);
+    if collects_errors {
+        data_pats.push(quote! { errors });
+    }
+    if args.catch_panics {
+        data_pats.push(quote! { panics });
+        data_pats.push(quote! { entities });
+    }
+    if args.metrics {
+        data_pats.push(quote! { metrics });
+    }
+    let data_pat = tuple_or_single(data_pats);
+    let channel_types = |lifetime: &TokenStream| -> Vec<TokenStream> {
+        args.channels
+            .iter()
+            .map(|channel| {
+                let event_type = &channel.event_type;
+                let channel_type = quote! { #crate_name::specs::shrev::EventChannel<#event_type> };
+                match channel.kind {
+                    ChannelKind::Emits => quote! { #crate_name::specs::Write<#lifetime, #channel_type> },
+                    ChannelKind::Reads => quote! { #crate_name::specs::Read<#lifetime, #channel_type> },
+                }
+            })
+            .collect()
+    };
+    let data_type = |lifetime: TokenStream| {
+        let mut data_types = vec![quote! { #system_data<#lifetime> }];
+        if state_def.is_some() {
+            data_types.push(quote! { #crate_name::specs::Write<#lifetime, #state_type> });
+        }
+        data_types.extend(channel_types(&lifetime));
+        data_types.extend(hooks.iter().map(|hook| hook_type(&lifetime, hook)));
+        if collects_errors {
+            data_types.push(quote! { #crate_name::specs::Read<#lifetime, #crate_name::SystemErrors> });
+        }
+        if args.catch_panics {
+            data_types.push(quote! { #crate_name::specs::Read<#lifetime, #crate_name::SystemPanics> });
+            data_types.push(quote! { #crate_name::specs::Entities<#lifetime> });
+        }
+        if args.metrics {
+            data_types.push(quote! { #crate_name::specs::Read<#lifetime, #crate_name::SystemMetrics> });
+        }
+        tuple_or_single(data_types)
+    };
+    let system_data_type = data_type(quote! { 'a });
+    let system_data_setup_type = data_type(quote! { '_ });
+    let mut access_types = vec![quote! { #system_data<'static> }];
+    access_types.extend(channel_types(&quote! { 'static }));
+    access_types.extend(hooks.iter().map(|hook| hook_type(&quote! { 'static }, hook)));
+    let access_type = tuple_or_single(access_types);
+    // The change readers of the data and the hooks are kept in the tracker field of the system, in this order.
+    let collect_changes = args.changes.as_ref().map(|tracker| {
+        quote! {
+            {
+                let mut readers = self.#tracker.readers();
+                #crate_name::TrackedData::collect_changes(&mut data, &mut readers);
+                #(#collect_hooks)*
+            }
+        }
+    });
+    let register_readers = args.changes.as_ref().map(|tracker| {
+        quote! {
+            self.#tracker.clear();
+            <#system_data<'_> as #crate_name::TrackedData>::register_readers(world, &mut self.#tracker);
+        }
+    });
+
+    let run_call = run_method.as_ref().map(|run_method| {
+        let run_call = quote! { self.#run_method(data #(, #event_args)*) };
+        let run_call = match &on_error {
+            Some(on_error) => quote! {
+                if let Err(error) = #run_call {
+                    let error = #crate_name::SystemError::new(#system_name, &error);
+                    #on_error
+                }
+            },
+            None => quote! { #run_call; },
+        };
+        quote! {
+            #(#event_reads)*
+            #run_call
+        }
+    });
+
+    let run_body = quote! {
+        #collect_changes
+        #(#hook_calls)*
+        #run_call
+    };
+    // A caught panic is stored with the system name and the entity being processed.
+    let run_body = if args.catch_panics {
+        quote! {
+            let scope = #crate_name::PanicScope::start();
+            let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+                #run_body
+            }));
+            if let Err(payload) = result {
+                panics.push(scope.caught(#system_name, payload, &entities));
+            }
+        }
+    } else {
+        run_body
+    };
+    // Metered runs are timed after the run conditions, so skipped runs are not recorded.
+    let run_body = if args.metrics {
+        quote! {
+            let started = ::std::time::Instant::now();
+            let items = #crate_name::RunItems::start();
+            #run_body
+            metrics.record(#system_name, started.elapsed(), items.count());
+        }
+    } else {
+        run_body
+    };
+    let run_body = quote! {
+        let _span = #crate_name::__system_span!(#system_name);
+        #(#guards)*
+        #run_body
+    };
+    // The access metadata requires `SystemAccess` of the data, so only systems added to a graph declare it.
+    let graph_impl = if args.graph {
+        Some(quote! {
+            impl #crate_name::DslSystem for #system_type {
+                const NAME: &'static str = #system_name;
+                const THREAD_LOCAL: bool = #thread_local;
+
+                fn accesses() -> Vec<#crate_name::Access> {
+                    <#access_type as #crate_name::SystemAccess>::accesses()
+                }
+
+                fn missing(world: &#crate_name::specs::World) -> Vec<#crate_name::Access> {
+                    <#access_type as #crate_name::SystemAccess>::missing(world)
+                }
+            }
+        })
+    } else {
+        None
+    };
+
+    let system_impl = if args.batch {
+        let run_method = run_method.expect("Cannot find the run-annotated method");
+        let tracker_methods = args.changes.as_ref().map(|tracker| {
+            quote! {
+                fn register_readers(&mut self, world: &mut #crate_name::specs::World) {
+                    #register_readers
+                }
+
+                fn collect_changes(&mut self, data: &mut #crate_name::SystemDataType<'_, Self>) {
+                    #crate_name::TrackedData::collect_changes(data, &mut self.#tracker.readers());
+                }
+            }
+        });
+        quote! {
+            impl #crate_name::DslBatch for #system_type {
+                fn run_batch(&mut self, data: #crate_name::SystemDataType<'_, Self>) -> usize {
+                    let _span = #crate_name::__system_span!(#system_name);
+                    self.#run_method(data)
+                }
+
+                #tracker_methods
+
+                #running_time
+            }
+        }
+    } else if thread_local {
+        quote! {
+            impl<'a> #crate_name::specs::RunNow<'a> for #system_type {
+                fn run_now(&mut self, world: &'a #crate_name::specs::World) {
+                    let #data_pat = <#system_data_type as #crate_name::specs::SystemData<'a>>::fetch(world);
+                    #run_body
+                }
+
+                fn setup(&mut self, world: &mut #crate_name::specs::World) {
+                    <#system_data_setup_type as #crate_name::specs::SystemData<'_>>::setup(world);
+                    #register_readers
+                    #(#register_events)*
+                    #(#register_hooks)*
+                }
+            }
+        }
+    } else {
+        quote! {
+            impl<'a> #crate_name::specs::System<'a> for #system_type {
+                type SystemData = #system_data_type;
+
+                fn run(&mut self, #data_pat: Self::SystemData) {
+                    #run_body
+                }
+
+                #running_time
+
+                fn setup(&mut self, world: &mut #crate_name::specs::World) {
+                    <Self::SystemData as #crate_name::specs::SystemData<'a>>::setup(world);
+                    #register_readers
+                    #(#register_events)*
+                    #(#register_hooks)*
+                }
+            }
+        }
+    };
+
+    quote! {
+        #item
+
+        #state_def
+
+        #system_impl
+
+        impl<'a> #crate_name::DslSystemData<'a> for #system_type {
+            type Data = #system_data<'a>;
+        }
+
+        #graph_impl
+    }
+}
+
+/// Arguments of the `#[system]` attribute: the system data type followed by options.
+struct SystemArgs {
+    system_data: syn::Path,
+    thread_local: bool,
+    batch: bool,
+    catch_panics: bool,
+    metrics: bool,
+    cost: Option<syn::LitStr>,
+    on_error: Option<syn::LitStr>,
+    run_if: Option<syn::Expr>,
+    every: Option<u64>,
+    channels: Vec<ChannelArg>,
+    changes: Option<Ident>,
+    graph: bool,
+}
+
+enum ChannelKind {
+    Emits,
+    Reads,
+}
+
+struct ChannelArg {
+    kind: ChannelKind,
+    event_type: syn::Type,
+}
+
+impl Parse for SystemArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut system_data = None;
+        let mut thread_local = false;
+        let mut batch = false;
+        let mut catch_panics = false;
+        let mut metrics = false;
+        let mut cost = None;
+        let mut on_error = None;
+        let mut run_if = None;
+        let mut every = None;
+        let mut channels = vec![];
+        let mut changes = None;
+        let mut graph = false;
+
+        for arg in Punctuated::<AttrArg, Token![,]>::parse_terminated(input)? {
+            let name = arg.name.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+            match (name.as_str(), arg.value) {
+                ("thread_local", AttrArgValue::Flag) => thread_local = true,
+                ("batch", AttrArgValue::Flag) => batch = true,
+                ("catch_panics", AttrArgValue::Flag) => catch_panics = true,
+                ("metrics", AttrArgValue::Flag) => metrics = true,
+                ("graph", AttrArgValue::Flag) => graph = true,
+                ("cost", AttrArgValue::Value(value)) => cost = Some(syn::parse2(value.into_token_stream())?),
+                ("on_error", AttrArgValue::Value(value)) => on_error = Some(syn::parse2(value.into_token_stream())?),
+                ("run_if", AttrArgValue::Value(value)) => run_if = Some(*value),
+                ("changes", AttrArgValue::Value(value)) => changes = Some(syn::parse2(value.into_token_stream())?),
+                ("every", AttrArgValue::Value(value)) => {
+                    let value: syn::LitInt = syn::parse2(value.into_token_stream())?;
+                    match value.base10_parse::<u64>()? {
+                        0 => return Err(syn::Error::new_spanned(value, "The system period must be positive")),
+                        period => every = Some(period),
+                    }
+                }
+                ("emits", AttrArgValue::List(types)) => {
+                    channels.extend(types.into_iter().map(|event_type| ChannelArg {
+                        kind: ChannelKind::Emits,
+                        event_type,
+                    }))
+                }
+                ("reads", AttrArgValue::List(types)) => {
+                    channels.extend(types.into_iter().map(|event_type| ChannelArg {
+                        kind: ChannelKind::Reads,
+                        event_type,
+                    }))
+                }
+                (_, AttrArgValue::Flag) if system_data.is_none() => system_data = Some(arg.name),
+                _ => return Err(syn::Error::new_spanned(&arg.name, "Unsupported system option")),
+            }
+        }
+
+        Ok(Self {
+            system_data: system_data.ok_or_else(|| input.error("The system data type must be specified"))?,
+            thread_local,
+            batch,
+            catch_panics,
+            metrics,
+            cost,
+            on_error,
+            run_if,
+            every,
+            channels,
+            changes,
+            graph,
+        })
+    }
+}
+
+/// Arguments of the `#[data_item]` attribute.
+struct DataItemArgs {
+    bundle: Option<Ident>,
+    serialize: bool,
+    capture: bool,
+    hash: bool,
+}
+
+impl Parse for DataItemArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut bundle = None;
+        let mut serialize = false;
+        let mut capture = false;
+        let mut hash = false;
+
+        for arg in Punctuated::<AttrArg, Token![,]>::parse_terminated(input)? {
+            let name = arg.name.get_ident().map(|ident| ident.to_string()).unwrap_or_default();
+            match (name.as_str(), arg.value) {
+                ("bundle", AttrArgValue::Value(value)) => bundle = Some(syn::parse2(value.into_token_stream())?),
+                ("serialize", AttrArgValue::Flag) => serialize = true,
+                ("capture", AttrArgValue::Flag) => capture = true,
+                ("hash", AttrArgValue::Flag) => hash = true,
+                _ => return Err(syn::Error::new_spanned(&arg.name, "Unsupported data item option")),
+            }
+        }
+
+        Ok(Self {
+            bundle,
+            serialize,
+            capture,
+            hash,
+        })
+    }
+}
+
+struct AttrArg {
+    name: syn::Path,
+    value: AttrArgValue,
+}
+
+enum AttrArgValue {
+    Flag,
+    Value(Box<syn::Expr>),
+    List(Vec<syn::Type>),
+}
+
+impl Parse for AttrArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            AttrArgValue::Value(Box::new(input.parse()?))
+        } else if input.peek(syn::token::Paren) {
+            let content;
+            syn::parenthesized!(content in input);
+            AttrArgValue::List(
+                Punctuated::<syn::Type, Token![,]>::parse_terminated(&content)?
+                    .into_iter()
+                    .collect(),
+            )
+        } else {
+            AttrArgValue::Flag
+        };
+
+        Ok(Self { name, value })
+    }
+}
+
+/// specs implements `Join` for tuples of up to 18 elements. The limit is deliberately conservative,
+/// so longer tuples of data items are nested well before they reach the specs limit.
+const JOIN_TUPLE_LIMIT: usize = 16;
+
+/// Builds a tuple of the elements, nesting them in chunks if the tuple exceeds `JOIN_TUPLE_LIMIT`.
+fn nested_tuple(elems: Vec<TokenStream>) -> TokenStream {
+    if elems.len() <= JOIN_TUPLE_LIMIT {
+        return quote! { (#(#elems),*) };
+    }
+
+    let chunks = elems
+        .chunks(JOIN_TUPLE_LIMIT)
+        .map(|chunk| match chunk {
+            [elem] => elem.clone(),
+            chunk => quote! { (#(#chunk),*) },
+        })
+        .collect();
+    nested_tuple(chunks)
+}
+
+/// Index paths of the elements of a `nested_tuple`, e.g. `3` or `1.2`.
+fn tuple_paths(len: usize) -> Vec<TokenStream> {
+    if len <= JOIN_TUPLE_LIMIT {
+        return (0..len)
+            .map(|i| Literal::usize_unsuffixed(i).into_token_stream())
+            .collect();
+    }
+
+    // `usize::div_ceil` is too recent for the supported compilers.
+    #[allow(clippy::manual_div_ceil)]
+    let chunk_paths = tuple_paths((len + JOIN_TUPLE_LIMIT - 1) / JOIN_TUPLE_LIMIT);
+    (0..len)
+        .map(|i| {
+            let chunk = &chunk_paths[i / JOIN_TUPLE_LIMIT];
+            if len - i / JOIN_TUPLE_LIMIT * JOIN_TUPLE_LIMIT == 1 {
+                chunk.clone()
+            } else {
+                let i = Literal::usize_unsuffixed(i % JOIN_TUPLE_LIMIT);
+                quote! { #chunk.#i }
+            }
+        })
+        .collect()
+}
+
+fn tuple_or_single(mut elems: Vec<TokenStream>) -> TokenStream {
+    if elems.len() == 1 {
+        elems.remove(0)
+    } else {
+        quote! { (#(#elems),*) }
+    }
+}
+
+fn system_name(system_type: &syn::Type) -> String {
+    match system_type {
+        syn::Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string())
+            .expect("Cannot get the system type name"),
+        _ => panic!("The system must be a named type"),
+    }
+}
+
+fn parse_struct(input: TokenStream) -> syn::ItemStruct {
+    let item = syn::parse2(input).expect("Failed parse input token stream");
+    match item {
+        syn::Item::Struct(item) => item,
+        _ => panic!("The data item must be a struct"),
+    }
+}
+
+struct Lifetimes {
+    item_lifetime: syn::Lifetime,
+    ext_lifetime: syn::Lifetime,
+    ext_generics: syn::Generics,
+}
+
+fn get_lifetimes(item: &syn::ItemStruct) -> Lifetimes {
+    let item_lifetime = match item
+        .generics
+        .params
+        .iter()
+        .next()
+        .expect("The data item must have at least one generic parameter with lifetime")
+    {
+        syn::GenericParam::Lifetime(def) => def.lifetime.clone(),
+        _ => panic!("The data item must have one lifetime parameter"),
+    };
+
+    let ext_lifetime = syn::Lifetime::new(&format!("'b{}", item_lifetime.ident), Span::call_site());
+    let mut ext_lifetime_def = syn::LifetimeDef::new(ext_lifetime.clone());
+    ext_lifetime_def.bounds.push(item_lifetime.clone());
+
+    let mut ext_params = vec![];
+    let mut is_lifetime_group_processed = false;
+    for param in &item.generics.params {
+        if let syn::GenericParam::Lifetime(_) = param {
+        } else {
+            ext_params.push(syn::GenericParam::Lifetime(ext_lifetime_def.clone()));
+            is_lifetime_group_processed = true;
+        }
+        ext_params.push(param.clone());
+    }
+    if !is_lifetime_group_processed {
+        ext_params.push(syn::GenericParam::Lifetime(ext_lifetime_def));
+    }
+    let mut ext_generics = item.generics.clone();
+    ext_generics.params = ext_params.into_iter().collect();
+
+    Lifetimes {
+        item_lifetime,
+        ext_lifetime,
+        ext_generics,
+    }
+}
+
+fn extract_attr(attrs: &mut Vec<syn::Attribute>, name: &str) -> Option<syn::Attribute> {
+    attrs
+        .iter()
+        .enumerate()
+        .find_map(|(i, attr)| if attr.is_name(name) { Some(i) } else { None })
+        .map(|idx| attrs.remove(idx))
+}
+
+/// `true` if the method returns a `Result`, `false` if it returns nothing or `()`.
+fn returns_result(item: &syn::ItemImpl, method: &Ident) -> syn::Result<bool> {
+    let output = item.items.iter().find_map(|item| match item {
+        syn::ImplItem::Method(impl_method) if impl_method.sig.ident == *method => Some(&impl_method.sig.output),
+        _ => None,
+    });
+    let ty = match output {
+        Some(syn::ReturnType::Type(_, ty)) => ty,
+        _ => return Ok(false),
+    };
+    match &**ty {
+        syn::Type::Tuple(tuple) if tuple.elems.is_empty() => Ok(false),
+        syn::Type::Path(path) if path.path.segments.last().map(|segment| segment.ident == "Result") == Some(true) => {
+            Ok(true)
+        }
+        _ => Err(syn::Error::new_spanned(
+            ty,
+            "The run-annotated method must return nothing or a `Result`",
+        )),
+    }
+}
+
+fn extract_method_with_attr(item: &mut syn::ItemImpl, name: &str) -> Option<Ident> {
+    item.items.iter_mut().find_map(|item| match item {
+        syn::ImplItem::Method(method) => {
+            if extract_attr(&mut method.attrs, name).is_some() {
+                Some(method.sig.ident.clone())
+            } else {
+                None
+            }
+        }
+        _ => None,
+    })
+}
+
+#[derive(Clone, Copy)]
+enum HookKind {
+    Insert,
+    Remove,
+}
+
+struct HookMethod {
+    kind: HookKind,
+    component: syn::Type,
+    method: Ident,
+}
+
+/// Extracts methods annotated with `#[on_insert(T)]` or `#[on_remove(T)]`.
+fn extract_hooks(item: &mut syn::ItemImpl) -> Vec<HookMethod> {
+    let mut hooks = vec![];
+    for item in &mut item.items {
+        if let syn::ImplItem::Method(method) = item {
+            for (name, kind) in &[("on_insert", HookKind::Insert), ("on_remove", HookKind::Remove)] {
+                while let Some(attr) = extract_attr(&mut method.attrs, name) {
+                    hooks.push(HookMethod {
+                        kind: *kind,
+                        component: attr.parse_args().expect("Cannot parse the hooked component type"),
+                        method: method.sig.ident.clone(),
+                    });
+                }
+            }
+        }
+    }
+    hooks
+}
+
+fn crate_name() -> Ident {
+    Ident::new(CRATE_NAME, Span::call_site())
+}
+
+enum ItemFieldKind {
+    Entity,
+    Component,
+    Resource,
+    MutComponent,
+    MutResource,
+    Removed,
+    Flatten,
+}
+
+impl ItemFieldKind {
+    fn is_mut(&self) -> bool {
+        // Flattened items may contain mutable fields, which cannot be known here.
+        matches!(
+            self,
+            ItemFieldKind::MutComponent | ItemFieldKind::MutResource | ItemFieldKind::Flatten
+        )
+    }
+}
+
+struct ItemFieldData {
+    kind: ItemFieldKind,
+    field_type: syn::Type,
+    filter: Option<TokenStream>,
+}
+
+fn extract_field_data(item: &mut syn::ItemStruct) -> Vec<ItemFieldData> {
+    item.fields
+        .iter_mut()
+        .map(|field| {
+            let (is_mut, field_type) = match &field.ty {
+                syn::Type::Reference(ref_type) => (ref_type.mutability.is_some(), (*ref_type.elem).clone()),
+                _ => (false, field.ty.clone()),
+            };
+            let is_entity = extract_attr(&mut field.attrs, "entity").is_some()
+                || format!("{}", field.ty.to_token_stream()).as_str() == "Entity";
+            let is_resource = extract_attr(&mut field.attrs, "resource").is_some();
+            let is_flatten = extract_attr(&mut field.attrs, "flatten").is_some();
+            let is_component =
+                extract_attr(&mut field.attrs, "component").is_some() || (!is_entity && !is_resource && !is_flatten);
+            let filter = change_filter(&mut field.attrs);
+            if filter.is_some() && !is_component {
+                panic!("Change filters are supported only for component fields");
+            }
+
+            if is_flatten {
+                return ItemFieldData {
+                    kind: ItemFieldKind::Flatten,
+                    field_type: field.ty.clone(),
+                    filter,
+                };
+            }
+
+            let kind = if is_component {
+                if is_mut {
+                    ItemFieldKind::MutComponent
+                } else {
+                    ItemFieldKind::Component
+                }
+            } else if is_resource {
+                if is_mut {
+                    ItemFieldKind::MutResource
+                } else {
+                    ItemFieldKind::Resource
+                }
+            } else if is_entity {
+                ItemFieldKind::Entity
+            } else {
+                unreachable!("Unsupported item kind")
+            };
+
+            ItemFieldData {
+                kind,
+                field_type,
+                filter,
+            }
+        })
+        .collect()
+}
+
+/// All fields of a resource item are references to resources.
+fn extract_resource_data(item: &syn::ItemStruct) -> Vec<ItemFieldData> {
+    item.fields
+        .iter()
+        .map(|field| match &field.ty {
+            syn::Type::Reference(ref_type) => ItemFieldData {
+                kind: if ref_type.mutability.is_some() {
+                    ItemFieldKind::MutResource
+                } else {
+                    ItemFieldKind::Resource
+                },
+                field_type: (*ref_type.elem).clone(),
+                filter: None,
+            },
+            _ => panic!("The fields of a resource item must be references to resources"),
+        })
+        .collect()
+}
+
+/// Joins items whose component is matched by any of the `#[inserted]` and `#[modified]` filters.
+fn change_filter(attrs: &mut Vec<syn::Attribute>) -> Option<TokenStream> {
+    let crate_name = crate_name();
+    let inserted = extract_attr(attrs, "inserted").is_some();
+    let modified = extract_attr(attrs, "modified").is_some();
+
+    match (inserted, modified) {
+        (true, true) => Some(quote! { (#crate_name::Inserted, #crate_name::Modified) }),
+        (true, false) => Some(quote! { #crate_name::Inserted }),
+        (false, true) => Some(quote! { #crate_name::Modified }),
+        (false, false) => None,
+    }
+}
+
+fn extract_removed_data(item: &mut syn::ItemStruct) -> Vec<ItemFieldData> {
+    let mut removed = vec![];
+    while let Some(attr) = extract_attr(&mut item.attrs, "removed") {
+        removed.push(ItemFieldData {
+            kind: ItemFieldKind::Removed,
+            field_type: attr.parse_args().expect("Cannot parse the removed component type"),
+            filter: None,
+        });
+    }
+    removed
+}
+
+fn storage_type(store_lifetime: &syn::Lifetime, field: &ItemFieldData) -> TokenStream {
+    let crate_name = crate_name();
+    let field_type = &field.field_type;
+
+    let storage = match field.kind {
+        ItemFieldKind::Entity => quote! { #crate_name::specs::Entities<#store_lifetime> },
+        ItemFieldKind::Component => quote! { #crate_name::specs::ReadStorage<#store_lifetime, #field_type> },
+        ItemFieldKind::Resource => quote! { #crate_name::specs::Read<#store_lifetime, #field_type> },
+        ItemFieldKind::MutComponent => quote! { #crate_name::specs::WriteStorage<#store_lifetime, #field_type> },
+        ItemFieldKind::MutResource => quote! { #crate_name::specs::Write<#store_lifetime, #field_type> },
+        ItemFieldKind::Removed => quote! { #crate_name::Removed<#store_lifetime, #field_type> },
+        ItemFieldKind::Flatten => {
+            let static_type = static_type(field_type);
+            quote! { <#static_type as #crate_name::ItemStorages<#store_lifetime>>::Storages }
+        }
+    };
+
+    match &field.filter {
+        Some(filter) => quote! { #crate_name::Changed<#store_lifetime, #storage, #filter> },
+        None => storage,
+    }
+}
+
+/// `ItemView` of a flattened field, which is implemented for the `'static` instance of the nested item.
+fn item_view(refs_lifetime: &syn::Lifetime, store_lifetime: &syn::Lifetime, field: &ItemFieldData) -> TokenStream {
+    let crate_name = crate_name();
+    let static_type = static_type(&field.field_type);
+    quote! { #static_type as #crate_name::ItemView<#refs_lifetime, #store_lifetime> }
+}
+
+fn static_type(ty: &syn::Type) -> syn::Type {
+    let mut ty = ty.clone();
+    if let syn::Type::Path(path) = &mut ty {
+        for segment in path.path.segments.iter_mut() {
+            if let syn::PathArguments::AngleBracketed(args) = &mut segment.arguments {
+                for arg in args.args.iter_mut() {
+                    if let syn::GenericArgument::Lifetime(lifetime) = arg {
+                        *lifetime = syn::Lifetime::new("'static", lifetime.span());
+                    }
+                }
+            }
+        }
+    }
+    ty
+}
+
+fn storages(
+    store_lifetime: &syn::Lifetime,
+    refs_lifetime: Option<&syn::Lifetime>,
+    fields: &[ItemFieldData],
+) -> TokenStream {
+    let storages: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            if let (ItemFieldKind::Flatten, Some(lifetime)) = (&field.kind, refs_lifetime) {
+                let nested = item_view(lifetime, store_lifetime, field);
+                return quote! { <#nested>::View };
+            }
+            let ref_part = match refs_lifetime {
+                Some(lifetime) if field.kind.is_mut() => quote! { &#lifetime mut },
+                Some(lifetime) => quote! { &#lifetime },
+                None => quote! {},
+            };
+            let storage = storage_type(store_lifetime, field);
+
+            quote! { #ref_part #storage }
+        })
+        .collect();
+
+    if storages.len() == 1 {
+        storages.into_iter().next().unwrap()
+    } else {
+        nested_tuple(storages)
+    }
+}
+
+struct MainViews {
+    view_type: TokenStream,
+    view_ret: TokenStream,
+    view_mut_type: TokenStream,
+    view_mut_ret: TokenStream,
+}
+
+fn storages_main_views(
+    store_lifetime: &syn::Lifetime,
+    refs_lifetime: &syn::Lifetime,
+    fields: &[ItemFieldData],
+) -> MainViews {
+    let paths = tuple_paths(fields.len());
+    let mut view_indexes = vec![];
+    let view_storages: Vec<_> = fields
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, field)| {
+            if field.kind.is_mut() {
+                None
+            } else {
+                view_indexes.push(idx);
+                let storage = storage_type(store_lifetime, field);
+                Some(quote! { &#refs_lifetime #storage })
+            }
+        })
+        .collect();
+
+    // With the `instrument` feature joinable views are instrumented, so the items of plain join loops of systems
+    // are counted for the metrics and traced. Otherwise the views are the plain joins.
+    let crate_name = crate_name();
+    let instrumented = |(view_type, view_ret): (TokenStream, TokenStream)| {
+        if cfg!(feature = "instrument") {
+            (
+                quote! { #crate_name::Instrumented<#view_type> },
+                quote! { #crate_name::Instrumented::new(#view_ret) },
+            )
+        } else {
+            (view_type, view_ret)
+        }
+    };
+
+    let (view_type, view_ret) = if view_storages.is_empty() {
+        (quote! { () }, quote! {})
+    } else if view_storages.len() == 1 {
+        let idx = &paths[view_indexes[0]];
+        instrumented((view_storages.into_iter().next().unwrap(), quote! { &self.#idx }))
+    } else {
+        let refs = view_indexes.iter().map(|&idx| {
+            let idx = &paths[idx];
+            quote! { &self.#idx }
+        });
+        instrumented((nested_tuple(view_storages), nested_tuple(refs.collect())))
+    };
+
+    let (view_mut_type, view_mut_ret) = if view_indexes.len() == fields.len() {
+        (quote! { () }, quote! {})
+    } else {
+        let ret_tuple_fields: Vec<_> = fields
+            .iter()
+            .zip(&paths)
+            .map(|(field, i)| {
+                if let ItemFieldKind::Flatten = field.kind {
+                    let nested = item_view(refs_lifetime, store_lifetime, field);
+                    quote! { <#nested>::view(&mut self.#i) }
+                } else if field.kind.is_mut() {
+                    quote! { &mut self.#i }
+                } else {
+                    quote! { &self.#i }
+                }
+            })
+            .collect();
+
+        let ret = if ret_tuple_fields.len() == 1 {
+            ret_tuple_fields.into_iter().next().unwrap()
+        } else {
+            nested_tuple(ret_tuple_fields)
+        };
+
+        instrumented((storages(store_lifetime, Some(refs_lifetime), fields), ret))
+    };
+
+    MainViews {
+        view_type,
+        view_ret,
+        view_mut_type,
+        view_mut_ret,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The views are instrumented with the `instrument` feature, see `test_expand_instrumented_views`.
+    #[cfg(not(feature = "instrument"))]
+    #[test]
+    fn test_expand_data_item() {
+        let item = quote! {
+            #[system_data(PosVelSystemData)]
+            #[derive(Clone, Copy)]
+            struct PosVel<'a> {
+                pos: &'a mut Pos,
+                vel: &'a Vel,
+            }
+        };
+        let output = expand_data_item(quote! {}, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+# [derive (Clone , Copy)] \
+struct PosVel < 'a > { \
+pos : & 'a mut Pos , \
+vel : & 'a Vel , \
+} \
+impl < 'a > From < (& 'a mut Pos , & 'a Vel) > for PosVel < 'a > { \
+fn from (t : (& 'a mut Pos , & 'a Vel)) -> Self { \
+Self { pos : t . 0 , vel : t . 1 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for PosVel < 'a > { \
+type Tuple = (& 'a mut Pos , & 'a Vel) ; \
+} \
+impl < 'a > PosVel < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+pos : storages . 0 . get_mut (entity) ? , vel : storages . 1 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report . check_component :: < Vel > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for PosVel < 'a > { \
+type View = (& 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for PosVel < 'static > { \
+type Storages = (specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for PosVel < 'static > { \
+type Item = PosVel < 'a > ; \
+type View = (& 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& mut storages . 0 , & storages . 1) \
+} \
+} \
+type PosVelSystemData < 'a > = (specs_dsl :: specs :: WriteStorage < 'a , Pos > , specs_dsl :: specs :: ReadStorage < 'a , Vel >) ; \
+# [doc = \" The joins of the system data. If a call is ambiguous with another main view trait in scope, e.g. for flattened storages resolved through `ItemStorages`, call it qualified: `PosVelSystemDataMainView::view_mut(&mut data)`.\"] pub trait PosVelSystemDataMainView < 'a > { \
+type ViewAllImmutable ; \
+type ViewAllWithMut ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable ; \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut ; \
+} \
+impl < 'a , 'b : 'a > PosVelSystemDataMainView < 'a > for PosVelSystemData < 'b > { \
+type ViewAllImmutable = & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel > ; \
+type ViewAllWithMut = (& 'a mut specs_dsl :: specs :: WriteStorage < 'b , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel >) ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable { \
+& self . 1 \
+} \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut { \
+(& mut self . 0 , & self . 1) \
+} \
+}");
+    }
+
+    #[cfg(feature = "instrument")]
+    #[test]
+    fn test_expand_instrumented_views() {
+        let item = quote! {
+            #[system_data(PosVelSystemData)]
+            struct PosVel<'a> {
+                pos: &'a mut Pos,
+                vel: &'a Vel,
+            }
+        };
+        let output = expand_data_item(quote! {}, item).to_string();
+
+        assert!(output.contains(
+            "type ViewAllImmutable = specs_dsl :: Instrumented < & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel > > ;"
+        ));
+        assert!(output.contains(
+            "fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut { \
+             specs_dsl :: Instrumented :: new ((& mut self . 0 , & self . 1)) }"
+        ));
+    }
+
+    // The views are instrumented with the `instrument` feature, see `test_expand_instrumented_views`.
+    #[cfg(not(feature = "instrument"))]
+    #[test]
+    fn test_expand_data_item_with_change_filters() {
+        let item = quote! {
+            #[system_data(MovedSystemData)]
+            #[removed(Target)]
+            struct Moved<'a> {
+                entity: Entity,
+                #[inserted]
+                #[modified]
+                pos: &'a Pos,
+            }
+        };
+        let output = expand_data_item(quote! {}, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+struct Moved < 'a > { \
+entity : Entity , \
+pos : & 'a Pos , \
+} \
+impl < 'a > From < (Entity , & 'a Pos) > for Moved < 'a > { \
+fn from (t : (Entity , & 'a Pos)) -> Self { \
+Self { entity : t . 0 , pos : t . 1 } \
+} \
+} \
+impl < 'a > From < (Entity , & 'a Pos , ()) > for Moved < 'a > { \
+fn from (t : (Entity , & 'a Pos , ())) -> Self { \
+Self { entity : t . 0 , pos : t . 1 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for Moved < 'a > { \
+type Tuple = (Entity , & 'a Pos , ()) ; \
+} \
+impl < 'a > Moved < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get < 'ba > (storages : & 'a (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: Changed < 'ba , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , specs_dsl :: Removed < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+if ! storages . 1 . mask () . contains (entity . id ()) { \
+return None ; \
+} \
+if ! storages . 2 . mask () . contains (entity . id ()) { \
+return None ; \
+} \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , pos : storages . 1 . get (entity) ? }) \
+} \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: Changed < 'ba , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , specs_dsl :: Removed < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+if ! storages . 1 . mask () . contains (entity . id ()) { \
+return None ; \
+} \
+if ! storages . 2 . mask () . contains (entity . id ()) { \
+return None ; \
+} \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , pos : storages . 1 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for Moved < 'a > { \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: Changed < 'ba , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , & 'a specs_dsl :: Removed < 'ba , Target >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for Moved < 'static > { \
+type Storages = (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: Changed < 'ba , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , specs_dsl :: Removed < 'ba , Target >) ; \
+const TRACKS_CHANGES : bool = true ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for Moved < 'static > { \
+type Item = Moved < 'a > ; \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: Changed < 'ba , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , & 'a specs_dsl :: Removed < 'ba , Target >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& storages . 0 , & storages . 1 , & storages . 2) \
+} \
+} \
+type MovedSystemData < 'a > = (specs_dsl :: specs :: Entities < 'a > , specs_dsl :: Changed < 'a , specs_dsl :: specs :: ReadStorage < 'a , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , specs_dsl :: Removed < 'a , Target >) ; \
+# [doc = \" The joins of the system data. If a call is ambiguous with another main view trait in scope, e.g. for flattened storages resolved through `ItemStorages`, call it qualified: `MovedSystemDataMainView::view_mut(&mut data)`.\"] pub trait MovedSystemDataMainView < 'a > { \
+type ViewAllImmutable ; \
+type ViewAllWithMut ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable ; \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut ; \
+} \
+impl < 'a , 'b : 'a > MovedSystemDataMainView < 'a > for MovedSystemData < 'b > { \
+type ViewAllImmutable = (& 'a specs_dsl :: specs :: Entities < 'b > , & 'a specs_dsl :: Changed < 'b , specs_dsl :: specs :: ReadStorage < 'b , Pos > , (specs_dsl :: Inserted , specs_dsl :: Modified) > , & 'a specs_dsl :: Removed < 'b , Target >) ; \
+type ViewAllWithMut = () ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable { \
+(& self . 0 , & self . 1 , & self . 2) \
+} \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut { } \
+}");
+    }
+
+    #[test]
+    fn test_expand_data_item_with_bundle() {
+        let item = quote! {
+            pub struct PosChange<'a> {
+                pub entity: Entity,
+                pub position: &'a mut Pos,
+                pub velocity: &'a Vel,
+            }
+        };
+        let output = expand_data_item(quote! { bundle = PosChangeBundle }, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+pub struct PosChange < 'a > { \
+pub entity : Entity , \
+pub position : & 'a mut Pos , \
+pub velocity : & 'a Vel , \
+} \
+impl < 'a > From < (Entity , & 'a mut Pos , & 'a Vel) > for PosChange < 'a > { \
+fn from (t : (Entity , & 'a mut Pos , & 'a Vel)) -> Self { \
+Self { entity : t . 0 , position : t . 1 , velocity : t . 2 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for PosChange < 'a > { \
+type Tuple = (Entity , & 'a mut Pos , & 'a Vel) ; \
+} \
+impl < 'a > PosChange < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get_mut (entity) ? , velocity : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report . check_component :: < Vel > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for PosChange < 'a > { \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for PosChange < 'static > { \
+type Storages = (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for PosChange < 'static > { \
+type Item = PosChange < 'a > ; \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& storages . 0 , & mut storages . 1 , & storages . 2) \
+} \
+} \
+pub struct PosChangeBundle { \
+pub position : Pos , \
+pub velocity : Vel \
+} \
+impl PosChangeBundle { \
+# [doc = r\" Creates an entity with the bundle components, registering their storages if needed.\"] pub fn spawn (self , world : & mut specs_dsl :: specs :: World) -> specs_dsl :: specs :: Entity { \
+< Self as specs_dsl :: ComponentBundle > :: register (world) ; \
+let entity = specs_dsl :: specs :: WorldExt :: entities (world) . create () ; \
+specs_dsl :: ComponentBundle :: insert_into (self , world , entity) ; \
+entity \
+} \
+# [doc = r\" Inserts the bundle components into the entity, e.g. from a system which writes the storages.\"] # [allow (clippy :: too_many_arguments)] pub fn insert_into (self , entity : specs_dsl :: specs :: Entity , position : & mut specs_dsl :: specs :: WriteStorage < '_ , Pos > , velocity : & mut specs_dsl :: specs :: WriteStorage < '_ , Vel >) -> Result < () , specs_dsl :: specs :: error :: Error > { \
+position . insert (entity , self . position) ? ; \
+velocity . insert (entity , self . velocity) ? ; \
+Ok (()) \
+} \
+# [doc = r\" Creates an entity whose components are inserted on the next `World::maintain`.\"] pub fn lazy_spawn (self , entities : & specs_dsl :: specs :: world :: EntitiesRes , lazy : & specs_dsl :: specs :: LazyUpdate) -> specs_dsl :: specs :: Entity { \
+let entity = entities . create () ; \
+self . lazy_insert (entity , lazy) ; \
+entity \
+} \
+# [doc = r\" Inserts the bundle components into the entity on the next `World::maintain`.\"] pub fn lazy_insert (self , entity : specs_dsl :: specs :: Entity , lazy : & specs_dsl :: specs :: LazyUpdate) { \
+lazy . insert (entity , self . position) ; \
+lazy . insert (entity , self . velocity) ; \
+} \
+} \
+impl specs_dsl :: ComponentBundle for PosChangeBundle { \
+fn register (world : & mut specs_dsl :: specs :: World) { \
+< specs_dsl :: specs :: WriteStorage < Pos > as specs_dsl :: specs :: SystemData > :: setup (world) ; \
+< specs_dsl :: specs :: WriteStorage < Vel > as specs_dsl :: specs :: SystemData > :: setup (world) ; \
+} \
+fn insert_into (self , world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) { \
+specs_dsl :: specs :: WorldExt :: write_storage :: < Pos > (world) . insert (entity , self . position) . expect (\"Cannot insert a component into a dead entity\") ; \
+specs_dsl :: specs :: WorldExt :: write_storage :: < Vel > (world) . insert (entity , self . velocity) . expect (\"Cannot insert a component into a dead entity\") ; \
+} \
+}");
+    }
+
+    #[test]
+    fn test_expand_data_item_with_capture() {
+        let item = quote! {
+            pub struct PosChange<'a> {
+                pub entity: Entity,
+                pub position: &'a mut Pos,
+                pub velocity: &'a Vel,
+            }
+        };
+        let output = expand_data_item(quote! { capture }, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+pub struct PosChange < 'a > { \
+pub entity : Entity , \
+pub position : & 'a mut Pos , \
+pub velocity : & 'a Vel , \
+} \
+impl < 'a > From < (Entity , & 'a mut Pos , & 'a Vel) > for PosChange < 'a > { \
+fn from (t : (Entity , & 'a mut Pos , & 'a Vel)) -> Self { \
+Self { entity : t . 0 , position : t . 1 , velocity : t . 2 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for PosChange < 'a > { \
+type Tuple = (Entity , & 'a mut Pos , & 'a Vel) ; \
+} \
+impl < 'a > PosChange < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get_mut (entity) ? , velocity : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report . check_component :: < Vel > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for PosChange < 'a > { \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for PosChange < 'static > { \
+type Storages = (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: WriteStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for PosChange < 'static > { \
+type Item = PosChange < 'a > ; \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& storages . 0 , & mut storages . 1 , & storages . 2) \
+} \
+} \
+# [doc = r\" The cloned components of an entity, see `capture` of the data item.\"] # [derive (Clone , Debug , PartialEq)] pub struct PosChangeCapture { \
+pub position : Pos , pub velocity : Vel \
+} \
+impl specs_dsl :: CaptureRecord for PosChangeCapture { \
+fn changed_fields (& self , other : & Self) -> Vec < & 'static str > { \
+let mut fields = vec ! [] ; \
+if self . position != other . position { \
+fields . push (\"position\") ; \
+} \
+if self . velocity != other . velocity { \
+fields . push (\"velocity\") ; \
+} \
+fields \
+} \
+} \
+impl < 'a > PosChange < 'a > { \
+# [doc = r\" Clones the components of the entities matching the item.\"] pub fn capture (world : & specs_dsl :: specs :: World) -> specs_dsl :: Capture < PosChangeCapture > { \
+let entities = specs_dsl :: specs :: WorldExt :: entities (world) ; \
+let position_storage = specs_dsl :: specs :: WorldExt :: read_storage :: < Pos > (world) ; \
+let velocity_storage = specs_dsl :: specs :: WorldExt :: read_storage :: < Vel > (world) ; \
+specs_dsl :: Capture { \
+items : specs_dsl :: specs :: Join :: join ((& entities , & position_storage , & velocity_storage)) . map (| joined | { \
+let record = PosChangeCapture { \
+position : Clone :: clone (joined . 1) , velocity : Clone :: clone (joined . 2) \
+} \
+; \
+(joined . 0 , record) }) . collect () , \
+} \
+} \
+}");
+    }
+
+    #[test]
+    fn test_expand_data_item_with_hash() {
+        let item = quote! {
+            pub struct Tracker<'a> {
+                pub entity: Entity,
+                pub position: &'a Pos,
+                pub target: &'a Target,
+            }
+        };
+        let output = expand_data_item(quote! { hash }, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+pub struct Tracker < 'a > { \
+pub entity : Entity , \
+pub position : & 'a Pos , \
+pub target : & 'a Target , \
+} \
+impl < 'a > From < (Entity , & 'a Pos , & 'a Target) > for Tracker < 'a > { \
+fn from (t : (Entity , & 'a Pos , & 'a Target)) -> Self { \
+Self { entity : t . 0 , position : t . 1 , target : t . 2 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for Tracker < 'a > { \
+type Tuple = (Entity , & 'a Pos , & 'a Target) ; \
+} \
+impl < 'a > Tracker < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get < 'ba > (storages : & 'a (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get (entity) ? , target : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get (entity) ? , target : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report . check_component :: < Target > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for Tracker < 'a > { \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for Tracker < 'static > { \
+type Storages = (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for Tracker < 'static > { \
+type Item = Tracker < 'a > ; \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& storages . 0 , & storages . 1 , & storages . 2) \
+} \
+} \
+impl < 'a > Tracker < 'a > { \
+# [doc = r\" Hashes the entities matching the item and their components in the entity order.\"] pub fn hash_state (world : & specs_dsl :: specs :: World , mut state : & mut dyn std :: hash :: Hasher) { \
+let entities = specs_dsl :: specs :: WorldExt :: entities (world) ; \
+let position_storage = specs_dsl :: specs :: WorldExt :: read_storage :: < Pos > (world) ; \
+let target_storage = specs_dsl :: specs :: WorldExt :: read_storage :: < Target > (world) ; \
+for joined in specs_dsl :: specs :: Join :: join ((& entities , & position_storage , & target_storage)) { \
+std :: hash :: Hash :: hash (& joined . 0 , & mut state) ; \
+std :: hash :: Hash :: hash (& joined . 1 , & mut state) ; \
+std :: hash :: Hash :: hash (& joined . 2 , & mut state) ; \
+} \
+} \
+}");
+    }
+
+    #[test]
+    fn test_expand_data_item_with_serialize() {
+        let item = quote! {
+            pub struct Tracker<'a> {
+                pub entity: Entity,
+                pub position: &'a Pos,
+                pub target: &'a Target,
+            }
+        };
+        let output = expand_data_item(quote! { serialize }, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+pub struct Tracker < 'a > { \
+pub entity : Entity , \
+pub position : & 'a Pos , \
+pub target : & 'a Target , \
+} \
+impl < 'a > From < (Entity , & 'a Pos , & 'a Target) > for Tracker < 'a > { \
+fn from (t : (Entity , & 'a Pos , & 'a Target)) -> Self { \
+Self { entity : t . 0 , position : t . 1 , target : t . 2 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for Tracker < 'a > { \
+type Tuple = (Entity , & 'a Pos , & 'a Target) ; \
+} \
+impl < 'a > Tracker < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get < 'ba > (storages : & 'a (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get (entity) ? , target : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+entity : Some (entity) . filter (| entity | storages . 0 . is_alive (* entity)) ? , position : storages . 1 . get (entity) ? , target : storages . 2 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_component :: < Pos > (world) ; \
+report . check_component :: < Target > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for Tracker < 'a > { \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for Tracker < 'static > { \
+type Storages = (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for Tracker < 'static > { \
+type Item = Tracker < 'a > ; \
+type View = (& 'a specs_dsl :: specs :: Entities < 'ba > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Target >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(& storages . 0 , & storages . 1 , & storages . 2) \
+} \
+} \
+# [doc = r\" The saved components of an entity, see `save` and `load` of the data item.\"] # [derive (specs_dsl :: serde :: Serialize , specs_dsl :: serde :: Deserialize)] # [serde (crate = \"specs_dsl::serde\")] pub struct TrackerRecord { \
+pub marker : specs_dsl :: SaveMarker , pub position : < Pos as specs_dsl :: specs :: saveload :: ConvertSaveload < specs_dsl :: SaveMarker >> :: Data , pub target : < Target as specs_dsl :: specs :: saveload :: ConvertSaveload < specs_dsl :: SaveMarker >> :: Data \
+} \
+impl < 'a > Tracker < 'a > { \
+# [doc = r\" Saves the entities matching the item, marking them and the entities their components refer to.\"] pub fn save < 'ba > (storages : & (specs_dsl :: specs :: Entities < 'ba > , specs_dsl :: specs :: ReadStorage < 'ba , Pos > , specs_dsl :: specs :: ReadStorage < 'ba , Target >) , (entities , markers , allocator) : & mut specs_dsl :: SaveMarkers < 'ba > ,) -> Result < specs_dsl :: Snapshot < TrackerRecord > , specs_dsl :: SnapshotError > { \
+let mut items = vec ! [] ; \
+for joined in specs_dsl :: specs :: Join :: join ((& * entities , & storages . 1 , & storages . 2)) { \
+let mut ids = | entity | specs_dsl :: specs :: saveload :: MarkerAllocator :: mark (& mut * * allocator , entity , markers) . map (| (marker , _) | * marker) ; \
+items . push (TrackerRecord { \
+marker : ids (joined . 0) . expect (\"Joined entities are alive\") , position : specs_dsl :: specs :: saveload :: ConvertSaveload :: convert_into (joined . 1 , & mut ids) . map_err (specs_dsl :: SnapshotError :: convert) ? , target : specs_dsl :: specs :: saveload :: ConvertSaveload :: convert_into (joined . 2 , & mut ids) . map_err (specs_dsl :: SnapshotError :: convert) ? }) ; \
+} \
+Ok (specs_dsl :: Snapshot { \
+items }) \
+} \
+# [doc = r\" Loads the saved entities, reusing the entities which have the same markers in the world.\"] pub fn load (world : & mut specs_dsl :: specs :: World , snapshot : specs_dsl :: Snapshot < TrackerRecord > ,) -> Result < Vec < specs_dsl :: specs :: Entity > , specs_dsl :: SnapshotError > { \
+specs_dsl :: register_save_markers (world) ; \
+specs_dsl :: specs :: WorldExt :: register :: < Pos > (world) ; \
+specs_dsl :: specs :: WorldExt :: register :: < Target > (world) ; \
+let entities = specs_dsl :: specs :: WorldExt :: entities (world) ; \
+let mut markers = specs_dsl :: specs :: WorldExt :: write_storage :: < specs_dsl :: SaveMarker > (world) ; \
+let mut allocator = specs_dsl :: specs :: WorldExt :: write_resource :: < specs_dsl :: SaveMarkerAllocator > (world) ; \
+let mut position_storage = specs_dsl :: specs :: WorldExt :: write_storage :: < Pos > (world) ; \
+let mut target_storage = specs_dsl :: specs :: WorldExt :: write_storage :: < Target > (world) ; \
+let mut loaded = Vec :: with_capacity (snapshot . items . len ()) ; \
+for record in snapshot . items { \
+let entity = specs_dsl :: specs :: saveload :: MarkerAllocator :: retrieve_entity (& mut * allocator , record . marker , & mut markers , & entities) ; \
+let mut ids = | marker | Some (specs_dsl :: specs :: saveload :: MarkerAllocator :: retrieve_entity (& mut * allocator , marker , & mut markers , & entities)) ; \
+let position = specs_dsl :: specs :: saveload :: ConvertSaveload :: convert_from (record . position , & mut ids) . map_err (specs_dsl :: SnapshotError :: convert) ? ; \
+position_storage . insert (entity , position) ? ; \
+let target = specs_dsl :: specs :: saveload :: ConvertSaveload :: convert_from (record . target , & mut ids) . map_err (specs_dsl :: SnapshotError :: convert) ? ; \
+target_storage . insert (entity , target) ? ; \
+loaded . push (entity) ; \
+} \
+Ok (loaded) \
+} \
+}");
+    }
+
+    // The views are instrumented with the `instrument` feature, see `test_expand_instrumented_views`.
+    #[cfg(not(feature = "instrument"))]
+    #[test]
+    fn test_expand_data_item_with_flatten() {
+        let item = quote! {
+            #[system_data(MoveSystemData)]
+            struct Move<'a> {
+                #[flatten]
+                transform: Transform<'a>,
+                vel: &'a Vel,
+            }
+        };
+        let output = expand_data_item(quote! {}, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+struct Move < 'a > { \
+transform : Transform < 'a > , \
+vel : & 'a Vel , \
+} \
+impl < 'a > From < (< Transform < 'a > as specs_dsl :: ItemTuple > :: Tuple , & 'a Vel) > for Move < 'a > { \
+fn from (t : (< Transform < 'a > as specs_dsl :: ItemTuple > :: Tuple , & 'a Vel)) -> Self { \
+Self { transform : t . 0 . into () , vel : t . 1 } \
+} \
+} \
+impl < 'a > specs_dsl :: ItemTuple for Move < 'a > { \
+type Tuple = (< Transform < 'a > as specs_dsl :: ItemTuple > :: Tuple , & 'a Vel) ; \
+} \
+impl < 'a > Move < 'a > { \
+# [doc = r\" The item of the entity, or `None` if the entity is dead or doesn't match the item.\"] pub fn get_mut < 'ba > (storages : & 'a mut (< Transform < 'static > as specs_dsl :: ItemStorages < 'ba >> :: Storages , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) , entity : specs_dsl :: specs :: Entity) -> Option < Self > { \
+Some (Self { \
+transform : < Transform < 'a > > :: get_mut (& mut storages . 0 , entity) ? , vel : storages . 1 . get (entity) ? }) \
+} \
+# [doc = r\" Explains whether the entity matches the item, e.g. for debug tooling and test failures.\"] pub fn diagnose (world : & specs_dsl :: specs :: World , entity : specs_dsl :: specs :: Entity) -> specs_dsl :: MatchReport { \
+let mut report = specs_dsl :: MatchReport :: new :: < Self > (world , entity) ; \
+report . check_item (< Transform < 'a > > :: diagnose (world , entity)) ; \
+report . check_component :: < Vel > (world) ; \
+report \
+} \
+} \
+impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for Move < 'a > { \
+type View = (< Transform < 'static > as specs_dsl :: ItemView < 'a , 'ba > > :: View , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+} \
+impl < 'ba > specs_dsl :: ItemStorages < 'ba > for Move < 'static > { \
+type Storages = (< Transform < 'static > as specs_dsl :: ItemStorages < 'ba >> :: Storages , specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+const TRACKS_CHANGES : bool = < Transform < 'static > as specs_dsl :: ItemStorages < 'ba >> :: TRACKS_CHANGES ; \
+} \
+impl < 'a , 'ba > specs_dsl :: ItemView < 'a , 'ba > for Move < 'static > { \
+type Item = Move < 'a > ; \
+type View = (< Transform < 'static > as specs_dsl :: ItemView < 'a , 'ba > > :: View , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel >) ; \
+fn view (storages : & 'a mut Self :: Storages) -> Self :: View { \
+(< Transform < 'static > as specs_dsl :: ItemView < 'a , 'ba > > :: view (& mut storages . 0) , & storages . 1) \
+} \
+} \
+type MoveSystemData < 'a > = (< Transform < 'static > as specs_dsl :: ItemStorages < 'a >> :: Storages , specs_dsl :: specs :: ReadStorage < 'a , Vel >) ; \
+# [doc = \" The joins of the system data. If a call is ambiguous with another main view trait in scope, e.g. for flattened storages resolved through `ItemStorages`, call it qualified: `MoveSystemDataMainView::view_mut(&mut data)`.\"] pub trait MoveSystemDataMainView < 'a > { \
+type ViewAllImmutable ; \
+type ViewAllWithMut ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable ; \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut ; \
+} \
+impl < 'a , 'b : 'a > MoveSystemDataMainView < 'a > for MoveSystemData < 'b > { \
+type ViewAllImmutable = & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel > ; \
+type ViewAllWithMut = (< Transform < 'static > as specs_dsl :: ItemView < 'a , 'b > > :: View , & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel >) ; \
+fn view (& 'a self) -> Self :: ViewAllImmutable { \
+& self . 1 \
+} \
+fn view_mut (& 'a mut self) -> Self :: ViewAllWithMut { \
+(< Transform < 'static > as specs_dsl :: ItemView < 'a , 'b > > :: view (& mut self . 0) , & self . 1) \
+} \
+}");
+    }
+
+    #[test]
+    fn test_nested_tuple() {
+        let elems: Vec<_> = (0..18)
+            .map(|i| Ident::new(&format!("T{}", i), Span::call_site()).into_token_stream())
+            .collect();
+        let output = nested_tuple(elems).to_string();
+        assert_eq!(
+            output,
+            "((T0 , T1 , T2 , T3 , T4 , T5 , T6 , T7 , T8 , T9 , T10 , T11 , T12 , T13 , T14 , T15) , (T16 , T17))"
+        );
+
+        let paths: Vec<_> = tuple_paths(33).into_iter().map(|path| path.to_string()).collect();
+        assert_eq!(paths[0], "0 . 0");
+        assert_eq!(paths[17], "1 . 1");
+        assert_eq!(paths[32], "2");
+    }
+
+    #[test]
+    fn test_expand_resource_item() {
+        let item = quote! {
+            #[system_data(FrameData)]
+            pub struct Frame<'a> {
+                pub dt: &'a DeltaTime,
+                pub score: &'a mut Score,
+            }
+        };
+        let output = expand_resource_item(item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+pub struct Frame < 'a > { \
+pub dt : & 'a DeltaTime , \
+pub score : & 'a mut Score , \
+} \
+impl < 'a , 'ba : 'a > From < & 'a mut (specs_dsl :: specs :: Read < 'ba , DeltaTime > , specs_dsl :: specs :: Write < 'ba , Score >) > for Frame < 'a > { \
+fn from (data : & 'a mut (specs_dsl :: specs :: Read < 'ba , DeltaTime > , specs_dsl :: specs :: Write < 'ba , Score >)) -> Self { \
+Self { dt : & * data . 0 , score : & mut * data . 1 } \
+} \
+} \
+pub type FrameData < 'a > = (specs_dsl :: specs :: Read < 'a , DeltaTime > , specs_dsl :: specs :: Write < 'a , Score >) ;");
+    }
+
+    #[test]
+    fn test_expand_system() {
+        let attrs = quote! { Test };
+        let item = quote! {
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, mut data: SystemDataType<Self>) {
+                    unimplemented!()
+                }
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl PhysicsSystem { \
+fn change_pos (& mut self , mut data : SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
+type SystemData = Test < 'a > ; \
+fn run (& mut self , data : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+self . change_pos (data) ; \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
+}");
+    }
 
-            let store = match field.kind {
-                ItemFieldKind::Entity => Some(quote! { &#refs_lifetime #crate_name::specs::Entities<#store_lifetime> }),
-                ItemFieldKind::Component => {
-                    Some(quote! { &#refs_lifetime #crate_name::specs::ReadStorage<#store_lifetime, #field_type> })
+    #[test]
+    fn test_expand_thread_local_system() {
+        let attrs = quote! { RenderData, thread_local };
+        let item = quote! {
+            impl RenderSystem {
+                #[run]
+                fn render(&mut self, data: SystemDataType<Self>) {
+                    unimplemented!()
                 }
-                ItemFieldKind::Resource => {
-                    Some(quote! { &#refs_lifetime #crate_name::specs::Read<#store_lifetime, #field_type> })
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl RenderSystem { \
+fn render (& mut self , data : SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+impl < 'a > specs_dsl :: specs :: RunNow < 'a > for RenderSystem { \
+fn run_now (& mut self , world : & 'a specs_dsl :: specs :: World) { \
+let data = < RenderData < 'a > as specs_dsl :: specs :: SystemData < 'a >> :: fetch (world) ; \
+let _span = specs_dsl :: __system_span ! (\"RenderSystem\") ; \
+self . render (data) ; \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< RenderData < '_ > as specs_dsl :: specs :: SystemData < '_ >> :: setup (world) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for RenderSystem { \
+type Data = RenderData < 'a > ; \
+}");
+    }
+
+    #[test]
+    fn test_expand_system_with_cost() {
+        let attrs = quote! { Test, cost = "long" };
+        let item = quote! {
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, data: SystemDataType<Self>) {
+                    unimplemented!()
                 }
-                _ => None,
-            };
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
 
-            if store.is_some() {
-                view_indexes.push(idx);
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl PhysicsSystem { \
+fn change_pos (& mut self , data : SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
+type SystemData = Test < 'a > ; \
+fn run (& mut self , data : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+self . change_pos (data) ; \
+} \
+fn running_time (& self) -> specs_dsl :: specs :: RunningTime { \
+specs_dsl :: specs :: RunningTime :: Long \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
+}");
+    }
+
+    #[test]
+    fn test_expand_batch_system() {
+        let attrs = quote! { SubStepData, batch };
+        let item = quote! {
+            impl PhysicsSubSteps {
+                #[run]
+                fn sub_steps(&mut self, data: SystemDataType<Self>) -> usize {
+                    unimplemented!()
+                }
             }
-            store
-        })
-        .collect();
+        };
+        let output = expand_system(attrs, item).to_string();
 
-    let (view_type, view_ret) = if view_storages.is_empty() {
-        (quote! { () }, quote! { () })
-    } else if view_storages.len() == 1 {
-        let idx = Literal::usize_unsuffixed(view_indexes[0]);
-        (view_storages.into_iter().next().unwrap(), quote! { &self.#idx })
-    } else {
-        let refs = view_indexes.iter().map(|&idx| {
-            let idx = Literal::usize_unsuffixed(idx);
-            quote! { &self.#idx }
-        });
-        (quote! { (#(#view_storages),*) }, quote! { (#(#refs),*) })
-    };
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl PhysicsSubSteps { \
+fn sub_steps (& mut self , data : SystemDataType < Self >) -> usize { \
+unimplemented ! () \
+} \
+} \
+impl specs_dsl :: DslBatch for PhysicsSubSteps { \
+fn run_batch (& mut self , data : specs_dsl :: SystemDataType < '_ , Self >) -> usize { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSubSteps\") ; \
+self . sub_steps (data) \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSubSteps { \
+type Data = SubStepData < 'a > ; \
+}");
+    }
 
-    let (view_mut_type, view_mut_ret) = if view_indexes.len() == fields.len() {
-        (quote! { () }, quote! { () })
-    } else {
-        let ret_tuple_fields: Vec<_> = fields
-            .iter()
-            .enumerate()
-            .map(|(i, field)| {
-                let i = Literal::usize_unsuffixed(i);
-                if field.kind.is_mut() {
-                    quote! { &mut self.#i }
-                } else {
-                    quote! { &self.#i }
+    #[test]
+    fn test_expand_system_with_run_conditions() {
+        let attrs = quote! { Test, run_if = physics_enabled, every = 2 };
+        let item = quote! {
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, data: SystemDataType<Self>) {
+                    unimplemented!()
                 }
-            })
-            .collect();
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
 
-        let ret = if ret_tuple_fields.len() == 1 {
-            ret_tuple_fields.into_iter().next().unwrap()
-        } else {
-            quote! { (#(#ret_tuple_fields),*) }
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl PhysicsSystem { \
+fn change_pos (& mut self , data : SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+# [doc (hidden)] # [derive (Default)] pub struct __PhysicsSystemDslState { \
+pub tick : u64 \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
+type SystemData = (Test < 'a > , specs_dsl :: specs :: Write < 'a , __PhysicsSystemDslState >) ; \
+fn run (& mut self , (data , mut state) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+let tick = state . tick ; \
+state . tick = tick . wrapping_add (1) ; \
+if tick % 2 != 0 { \
+return ; \
+} \
+if ! (physics_enabled) (& data) { \
+return ; \
+} \
+self . change_pos (data) ; \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
+}");
+    }
+
+    #[test]
+    fn test_expand_system_with_event_channels() {
+        let attrs = quote! { Test, emits(Explosion), reads(Hit), graph };
+        let item = quote! {
+            impl BombSystem {
+                #[run]
+                fn boom(&mut self, data: SystemDataType<Self>, explosions: Emitter<Explosion>, hits: Events<Hit>) {
+                    unimplemented!()
+                }
+            }
         };
+        let output = expand_system(attrs, item).to_string();
 
-        (storages(store_lifetime, Some(refs_lifetime), fields), ret)
-    };
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl BombSystem { \
+fn boom (& mut self , data : SystemDataType < Self > , explosions : Emitter < Explosion > , hits : Events < Hit >) { \
+unimplemented ! () \
+} \
+} \
+# [doc (hidden)] # [derive (Default)] pub struct __BombSystemDslState { \
+pub reader_1 : Option < specs_dsl :: specs :: ReaderId < Hit >> \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for BombSystem { \
+type SystemData = (Test < 'a > , specs_dsl :: specs :: Write < 'a , __BombSystemDslState > , specs_dsl :: specs :: Write < 'a , specs_dsl :: specs :: shrev :: EventChannel < Explosion > > , specs_dsl :: specs :: Read < 'a , specs_dsl :: specs :: shrev :: EventChannel < Hit > >) ; \
+fn run (& mut self , (data , mut state , mut channel_0 , channel_1) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"BombSystem\") ; \
+let events_1 = channel_1 . read (state . reader_1 . as_mut () . expect (\"The event reader is not registered, the system must be set up first\")) ; \
+self . boom (data , specs_dsl :: Emitter :: new (& mut channel_0) , events_1) ; \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+world . fetch_mut :: < __BombSystemDslState > () . reader_1 = Some (world . fetch_mut :: < specs_dsl :: specs :: shrev :: EventChannel < Hit >> () . register_reader ()) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for BombSystem { \
+type Data = Test < 'a > ; \
+} \
+impl specs_dsl :: DslSystem for BombSystem { \
+const NAME : & 'static str = \"BombSystem\" ; \
+const THREAD_LOCAL : bool = false ; \
+fn accesses () -> Vec < specs_dsl :: Access > { \
+< (Test < 'static > , specs_dsl :: specs :: Write < 'static , specs_dsl :: specs :: shrev :: EventChannel < Explosion > > , specs_dsl :: specs :: Read < 'static , specs_dsl :: specs :: shrev :: EventChannel < Hit > >) as specs_dsl :: SystemAccess > :: accesses () \
+} \
+fn missing (world : & specs_dsl :: specs :: World) -> Vec < specs_dsl :: Access > { \
+< (Test < 'static > , specs_dsl :: specs :: Write < 'static , specs_dsl :: specs :: shrev :: EventChannel < Explosion > > , specs_dsl :: specs :: Read < 'static , specs_dsl :: specs :: shrev :: EventChannel < Hit > >) as specs_dsl :: SystemAccess > :: missing (world) \
+} \
+}");
+    }
 
-    MainViews {
-        view_type,
-        view_ret,
-        view_mut_type,
-        view_mut_ret,
+    #[test]
+    fn test_expand_system_with_hooks() {
+        let attrs = quote! { Test, changes = changes, graph };
+        let item = quote! {
+            impl ShrapnelSystem {
+                #[on_insert(ClusterBomb)]
+                fn arm(&mut self, entity: Entity, data: &mut SystemDataType<Self>) {
+                    unimplemented!()
+                }
+
+                #[on_remove(ClusterBomb)]
+                fn spawn_shrapnel(&mut self, entity: Entity, data: &mut SystemDataType<Self>) {
+                    unimplemented!()
+                }
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl ShrapnelSystem { \
+fn arm (& mut self , entity : Entity , data : & mut SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+fn spawn_shrapnel (& mut self , entity : Entity , data : & mut SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for ShrapnelSystem { \
+type SystemData = (Test < 'a > , specs_dsl :: Hook < 'a , ClusterBomb , specs_dsl :: Inserted > , specs_dsl :: Hook < 'a , ClusterBomb , specs_dsl :: Removal >) ; \
+fn run (& mut self , (mut data , mut hook_0 , mut hook_1) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"ShrapnelSystem\") ; \
+{ \
+let mut readers = self . changes . readers () ; \
+specs_dsl :: TrackedData :: collect_changes (& mut data , & mut readers) ; \
+specs_dsl :: TrackedData :: collect_changes (& mut hook_0 , & mut readers) ; \
+specs_dsl :: TrackedData :: collect_changes (& mut hook_1 , & mut readers) ; \
+} \
+for entity in hook_0 . entities () { \
+specs_dsl :: __item_event ! (\"ShrapnelSystem\" , entity) ; \
+self . arm (entity , & mut data) ; \
+} \
+for entity in hook_1 . entities () { \
+specs_dsl :: __item_event ! (\"ShrapnelSystem\" , entity) ; \
+self . spawn_shrapnel (entity , & mut data) ; \
+} \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+self . changes . clear () ; \
+< Test < '_ > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+< specs_dsl :: Hook < '_ , ClusterBomb , specs_dsl :: Inserted > > :: check_access :: < Test < '_ >> (:: std :: any :: type_name :: < Self > ()) ; \
+< specs_dsl :: Hook < '_ , ClusterBomb , specs_dsl :: Inserted > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+< specs_dsl :: Hook < '_ , ClusterBomb , specs_dsl :: Removal > > :: check_access :: < Test < '_ >> (:: std :: any :: type_name :: < Self > ()) ; \
+< specs_dsl :: Hook < '_ , ClusterBomb , specs_dsl :: Removal > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for ShrapnelSystem { \
+type Data = Test < 'a > ; \
+} \
+impl specs_dsl :: DslSystem for ShrapnelSystem { \
+const NAME : & 'static str = \"ShrapnelSystem\" ; \
+const THREAD_LOCAL : bool = false ; \
+fn accesses () -> Vec < specs_dsl :: Access > { \
+< (Test < 'static > , specs_dsl :: Hook < 'static , ClusterBomb , specs_dsl :: Inserted > , specs_dsl :: Hook < 'static , ClusterBomb , specs_dsl :: Removal >) as specs_dsl :: SystemAccess > :: accesses () \
+} \
+fn missing (world : & specs_dsl :: specs :: World) -> Vec < specs_dsl :: Access > { \
+< (Test < 'static > , specs_dsl :: Hook < 'static , ClusterBomb , specs_dsl :: Inserted > , specs_dsl :: Hook < 'static , ClusterBomb , specs_dsl :: Removal >) as specs_dsl :: SystemAccess > :: missing (world) \
+} \
+}");
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_expand_fallible_system() {
+        let attrs = quote! { Test };
+        let item = quote! {
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, data: SystemDataType<Self>) -> Result<(), String> {
+                    unimplemented!()
+                }
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
+
+        #[rustfmt::skip]
+        assert_eq!(output, "\
+impl PhysicsSystem { \
+fn change_pos (& mut self , data : SystemDataType < Self >) -> Result < () , String > { \
+unimplemented ! () \
+} \
+} \
+impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
+type SystemData = (Test < 'a > , specs_dsl :: specs :: Read < 'a , specs_dsl :: SystemErrors >) ; \
+fn run (& mut self , (data , errors) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+if let Err (error) = self . change_pos (data) { \
+let error = specs_dsl :: SystemError :: new (\"PhysicsSystem\" , & error) ; \
+errors . push (error) ; \
+} \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
+}");
+    }
 
     #[test]
-    fn test_expand_data_item() {
+    fn test_expand_system_returning_non_result() {
+        let attrs = quote! { Test };
         let item = quote! {
-            #[system_data(PosVelSystemData)]
-            #[derive(Clone, Copy)]
-            struct PosVel<'a> {
-                pos: &'a mut Pos,
-                vel: &'a Vel,
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, data: SystemDataType<Self>) -> Option<()> {
+                    unimplemented!()
+                }
+            }
+        };
+        let output = expand_system(attrs, item).to_string();
+
+        assert_eq!(
+            output,
+            "compile_error ! { \"The run-annotated method must return nothing or a `Result`\" }"
+        );
+    }
+
+    #[test]
+    fn test_expand_system_returning_unit() {
+        let output = |ret| {
+            let item = quote! {
+                impl PhysicsSystem {
+                    #[run]
+                    fn change_pos(&mut self, data: SystemDataType<Self>) #ret {
+                        unimplemented!()
+                    }
+                }
+            };
+            expand_system(quote! { Test }, item).to_string()
+        };
+
+        assert_eq!(
+            output(quote! { -> () }),
+            output(quote! {}).replace(
+                "data : SystemDataType < Self >)",
+                "data : SystemDataType < Self >) -> ()"
+            )
+        );
+    }
+
+    #[test]
+    fn test_expand_system_catching_panics() {
+        let attrs = quote! { Test, catch_panics, changes = changes };
+        let item = quote! {
+            impl PhysicsSystem {
+                #[run]
+                fn change_pos(&mut self, data: SystemDataType<Self>) {
+                    unimplemented!()
+                }
+
+                #[on_insert(Pos)]
+                fn pos_inserted(&mut self, entity: Entity, data: &mut SystemDataType<Self>) {
+                    unimplemented!()
+                }
             }
         };
-        let output = expand_data_item(item).to_string();
+        let output = expand_system(attrs, item).to_string();
 
         #[rustfmt::skip]
         assert_eq!(output, "\
-# [ derive ( Clone , Copy ) ] \
-struct PosVel < 'a > { \
-pos : & 'a mut Pos , \
-vel : & 'a Vel , \
+impl PhysicsSystem { \
+fn change_pos (& mut self , data : SystemDataType < Self >) { \
+unimplemented ! () \
 } \
-impl < 'a > From < ( & 'a mut Pos , & 'a Vel ) > for PosVel < 'a > { \
-fn from ( t : ( & 'a mut Pos , & 'a Vel ) ) -> Self { \
-Self { pos : t . 0 , vel : t . 1 } \
+fn pos_inserted (& mut self , entity : Entity , data : & mut SystemDataType < Self >) { \
+unimplemented ! () \
 } \
 } \
-impl < 'a , 'ba : 'a > specs_dsl :: DataItem < 'a , 'ba > for PosVel < 'a > { \
-type View = ( & 'a mut specs_dsl :: specs :: WriteStorage < 'ba , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'ba , Vel > ) ; \
+impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
+type SystemData = (Test < 'a > , specs_dsl :: Hook < 'a , Pos , specs_dsl :: Inserted > , specs_dsl :: specs :: Read < 'a , specs_dsl :: SystemPanics > , specs_dsl :: specs :: Entities < 'a >) ; \
+fn run (& mut self , (mut data , mut hook_0 , panics , entities) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+let scope = specs_dsl :: PanicScope :: start () ; \
+let result = :: std :: panic :: catch_unwind (:: std :: panic :: AssertUnwindSafe (|| { \
+{ \
+let mut readers = self . changes . readers () ; \
+specs_dsl :: TrackedData :: collect_changes (& mut data , & mut readers) ; \
+specs_dsl :: TrackedData :: collect_changes (& mut hook_0 , & mut readers) ; \
 } \
-type PosVelSystemData < 'a > = ( specs_dsl :: specs :: WriteStorage < 'a , Pos > , specs_dsl :: specs :: ReadStorage < 'a , Vel > ) ; \
-pub trait PosVelSystemDataMainView < 'a > { \
-type ViewAllImmutable ; \
-type ViewAllWithMut ; \
-fn view ( & 'a self ) -> Self :: ViewAllImmutable ; \
-fn view_mut ( & 'a mut self ) -> Self :: ViewAllWithMut ; \
+for entity in hook_0 . entities () { \
+specs_dsl :: __item_event ! (\"PhysicsSystem\" , entity) ; \
+specs_dsl :: with_entity_context (entity , || self . pos_inserted (entity , & mut data)) ; \
 } \
-impl < 'a , 'b : 'a > PosVelSystemDataMainView < 'a > for PosVelSystemData < 'b > { \
-type ViewAllImmutable = & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel > ; \
-type ViewAllWithMut = ( & 'a mut specs_dsl :: specs :: WriteStorage < 'b , Pos > , & 'a specs_dsl :: specs :: ReadStorage < 'b , Vel > ) ; \
-fn view ( & 'a self ) -> Self :: ViewAllImmutable { \
-& self . 1 \
+self . change_pos (data) ; \
+})) ; \
+if let Err (payload) = result { \
+panics . push (scope . caught (\"PhysicsSystem\" , payload , & entities)) ; \
 } \
-fn view_mut ( & 'a mut self ) -> Self :: ViewAllWithMut { \
-( & mut self . 0 , & self . 1 ) \
 } \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+self . changes . clear () ; \
+< Test < '_ > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+< specs_dsl :: Hook < '_ , Pos , specs_dsl :: Inserted > > :: check_access :: < Test < '_ >> (:: std :: any :: type_name :: < Self > ()) ; \
+< specs_dsl :: Hook < '_ , Pos , specs_dsl :: Inserted > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+} \
+} \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
 }");
     }
 
     #[test]
-    fn test_expand_system() {
-        let attrs = quote! { Test };
+    fn test_expand_metered_system() {
+        let attrs = quote! { Test, metrics, every = 2, changes = changes };
         let item = quote! {
             impl PhysicsSystem {
                 #[run]
-                fn change_pos(&mut self, mut data: SystemDataType<Self>) {
+                fn change_pos(&mut self, data: SystemDataType<Self>) {
+                    unimplemented!()
+                }
+
+                #[on_insert(Pos)]
+                fn pos_inserted(&mut self, entity: Entity, data: &mut SystemDataType<Self>) {
                     unimplemented!()
                 }
             }
@@ -479,15 +3018,50 @@ fn view_mut ( & 'a mut self ) -> Self :: ViewAllWithMut { \
         #[rustfmt::skip]
         assert_eq!(output, "\
 impl PhysicsSystem { \
-fn change_pos ( & mut self , mut data : SystemDataType < Self > ) { \
-unimplemented ! ( ) \
+fn change_pos (& mut self , data : SystemDataType < Self >) { \
+unimplemented ! () \
 } \
+fn pos_inserted (& mut self , entity : Entity , data : & mut SystemDataType < Self >) { \
+unimplemented ! () \
+} \
+} \
+# [doc (hidden)] # [derive (Default)] pub struct __PhysicsSystemDslState { \
+pub tick : u64 \
 } \
 impl < 'a > specs_dsl :: specs :: System < 'a > for PhysicsSystem { \
-type SystemData = Test < 'a > ; \
-fn run ( & mut self , data : Self :: SystemData ) { \
-self . change_pos ( data ) ; \
+type SystemData = (Test < 'a > , specs_dsl :: specs :: Write < 'a , __PhysicsSystemDslState > , specs_dsl :: Hook < 'a , Pos , specs_dsl :: Inserted > , specs_dsl :: specs :: Read < 'a , specs_dsl :: SystemMetrics >) ; \
+fn run (& mut self , (mut data , mut state , mut hook_0 , metrics) : Self :: SystemData) { \
+let _span = specs_dsl :: __system_span ! (\"PhysicsSystem\") ; \
+let tick = state . tick ; \
+state . tick = tick . wrapping_add (1) ; \
+if tick % 2 != 0 { \
+return ; \
+} \
+let started = :: std :: time :: Instant :: now () ; \
+let items = specs_dsl :: RunItems :: start () ; \
+{ \
+let mut readers = self . changes . readers () ; \
+specs_dsl :: TrackedData :: collect_changes (& mut data , & mut readers) ; \
+specs_dsl :: TrackedData :: collect_changes (& mut hook_0 , & mut readers) ; \
+} \
+for entity in hook_0 . entities () { \
+specs_dsl :: __item_event ! (\"PhysicsSystem\" , entity) ; \
+self . pos_inserted (entity , & mut data) ; \
+items . add (1) ; \
+} \
+self . change_pos (data) ; \
+metrics . record (\"PhysicsSystem\" , started . elapsed () , items . count ()) ; \
+} \
+fn setup (& mut self , world : & mut specs_dsl :: specs :: World) { \
+< Self :: SystemData as specs_dsl :: specs :: SystemData < 'a >> :: setup (world) ; \
+self . changes . clear () ; \
+< Test < '_ > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+< specs_dsl :: Hook < '_ , Pos , specs_dsl :: Inserted > > :: check_access :: < Test < '_ >> (:: std :: any :: type_name :: < Self > ()) ; \
+< specs_dsl :: Hook < '_ , Pos , specs_dsl :: Inserted > as specs_dsl :: TrackedData > :: register_readers (world , & mut self . changes) ; \
+} \
 } \
+impl < 'a > specs_dsl :: DslSystemData < 'a > for PhysicsSystem { \
+type Data = Test < 'a > ; \
 }");
     }
 }
@@ -7,7 +7,7 @@ use specs_dsl::{
         Builder, Component, DenseVecStorage, DispatcherBuilder, Entities, Entity, HashMapStorage, Join, LazyUpdate,
         ParJoin, Read, ReadStorage, VecStorage, World, WorldExt, WriteStorage,
     },
-    system, SystemDataType,
+    system, SystemDataType, WorldQuery,
 };
 
 const TAU: f32 = 2. * std::f32::consts::PI;
@@ -46,7 +46,7 @@ impl PhysicsSystem {
     #[run]
     fn change_pos(&mut self, mut data: SystemDataType<Self>) {
         data.view_mut().par_join().for_each(|item| {
-            let mut item: PosChange = item.into();
+            let item: PosChange = item.into();
 
             item.position.0 += item.velocity.0;
             item.position.1 += item.velocity.1;
@@ -126,6 +126,11 @@ impl ShrapnelSystem {
     }
 }
 
+#[data_item]
+struct Rendered<'a> {
+    position: &'a Pos,
+}
+
 fn main() {
     let mut world = World::new();
 
@@ -146,24 +151,20 @@ fn main() {
     let mut step = 0;
     loop {
         step += 1;
-        let mut entities = 0;
+        let entities = world.entities().join().count();
         {
             // Simple console rendering
-            let positions = world.read_storage::<Pos>();
             const WIDTH: usize = 10;
             const HEIGHT: usize = 10;
             const SCALE: f32 = 1. / 4.;
             let mut screen = [[0; WIDTH]; HEIGHT];
-            for entity in world.entities().join() {
-                if let Some(pos) = positions.get(entity) {
-                    let x = (pos.0 * SCALE + WIDTH as f32 / 2.).floor() as usize;
-                    let y = (pos.1 * SCALE + HEIGHT as f32 / 2.).floor() as usize;
-                    if x < WIDTH && y < HEIGHT {
-                        screen[x][y] += 1;
-                    }
+            world.query::<Rendered, _>(|item| {
+                let x = (item.position.0 * SCALE + WIDTH as f32 / 2.).floor() as usize;
+                let y = (item.position.1 * SCALE + HEIGHT as f32 / 2.).floor() as usize;
+                if x < WIDTH && y < HEIGHT {
+                    screen[x][y] += 1;
                 }
-                entities += 1;
-            }
+            });
             println!("Step: {}, Entities: {}", step, entities);
             for row in &screen {
                 for cell in row {
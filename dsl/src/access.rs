@@ -0,0 +1,206 @@
+use std::any::{type_name, TypeId};
+
+use specs::{
+    shred::Resource, storage::MaskedStorage, world::EntitiesRes, Component, Read, ReadStorage, World, Write,
+    WriteStorage,
+};
+
+/// The kind of a single system data access. It mirrors the field kinds of a data item.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FieldKind {
+    Entity,
+    Component,
+    Resource,
+    MutComponent,
+    MutResource,
+}
+
+impl FieldKind {
+    pub fn is_mut(self) -> bool {
+        matches!(self, FieldKind::MutComponent | FieldKind::MutResource)
+    }
+
+    pub fn is_component(self) -> bool {
+        matches!(self, FieldKind::Component | FieldKind::MutComponent)
+    }
+
+    pub fn is_resource(self) -> bool {
+        matches!(self, FieldKind::Resource | FieldKind::MutResource)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Access {
+    pub kind: FieldKind,
+    pub type_name: &'static str,
+}
+
+impl Access {
+    pub fn new<T: ?Sized>(kind: FieldKind) -> Self {
+        Self {
+            kind,
+            type_name: type_name::<T>(),
+        }
+    }
+
+    /// Returns `true` if both accesses touch the same storage or resource and at least one of them mutates it.
+    pub fn conflicts_with(&self, other: &Access) -> bool {
+        self.is_same_target(other) && (self.kind.is_mut() || other.kind.is_mut())
+    }
+
+    pub fn is_same_target(&self, other: &Access) -> bool {
+        self.type_name == other.type_name && self.kind.is_component() == other.kind.is_component()
+    }
+
+    /// The type name without module paths, e.g. `EventChannel<Explosion>`.
+    pub fn short_name(&self) -> String {
+        short_type_name(self.type_name)
+    }
+}
+
+/// Describes what a system data type reads and writes.
+pub trait SystemAccess {
+    fn collect_accesses(accesses: &mut Vec<Access>);
+
+    /// Collects the accesses whose storages or resources are absent from the world, so fetching would panic.
+    fn collect_missing(world: &World, missing: &mut Vec<Access>);
+
+    fn accesses() -> Vec<Access> {
+        let mut accesses = vec![];
+        Self::collect_accesses(&mut accesses);
+        accesses
+    }
+
+    fn missing(world: &World) -> Vec<Access> {
+        let mut missing = vec![];
+        Self::collect_missing(world, &mut missing);
+        missing
+    }
+}
+
+impl SystemAccess for () {
+    fn collect_accesses(_accesses: &mut Vec<Access>) {}
+
+    fn collect_missing(_world: &World, _missing: &mut Vec<Access>) {}
+}
+
+/// Optional data is fetched as `None` if it is absent, so it is never missing.
+impl<T: SystemAccess> SystemAccess for Option<T> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        T::collect_accesses(accesses)
+    }
+
+    fn collect_missing(_world: &World, _missing: &mut Vec<Access>) {}
+}
+
+impl<'a, T: Resource, F> SystemAccess for Read<'a, T, F> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        if TypeId::of::<T>() == TypeId::of::<EntitiesRes>() {
+            accesses.push(Access::new::<EntitiesRes>(FieldKind::Entity));
+        } else {
+            accesses.push(Access::new::<T>(FieldKind::Resource));
+        }
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        if !world.has_value::<T>() {
+            Self::collect_accesses(missing);
+        }
+    }
+}
+
+impl<'a, T: Resource, F> SystemAccess for Write<'a, T, F> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access::new::<T>(FieldKind::MutResource));
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        if !world.has_value::<T>() {
+            Self::collect_accesses(missing);
+        }
+    }
+}
+
+impl<'a, T: Component> SystemAccess for ReadStorage<'a, T> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access::new::<T>(FieldKind::Component));
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        if !world.has_value::<MaskedStorage<T>>() {
+            Self::collect_accesses(missing);
+        }
+    }
+}
+
+impl<'a, T: Component> SystemAccess for WriteStorage<'a, T> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access::new::<T>(FieldKind::MutComponent));
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        if !world.has_value::<MaskedStorage<T>>() {
+            Self::collect_accesses(missing);
+        }
+    }
+}
+
+macro_rules! impl_system_access {
+    ($($ty:ident),*) => {
+        impl<$($ty: SystemAccess),*> SystemAccess for ($($ty,)*) {
+            fn collect_accesses(accesses: &mut Vec<Access>) {
+                $($ty::collect_accesses(accesses);)*
+            }
+
+            fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+                $($ty::collect_missing(world, missing);)*
+            }
+        }
+    };
+}
+
+impl_system_access!(A);
+impl_system_access!(A, B);
+impl_system_access!(A, B, C);
+impl_system_access!(A, B, C, D);
+impl_system_access!(A, B, C, D, E);
+impl_system_access!(A, B, C, D, E, F);
+impl_system_access!(A, B, C, D, E, F, G);
+impl_system_access!(A, B, C, D, E, F, G, H);
+impl_system_access!(A, B, C, D, E, F, G, H, I);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y);
+impl_system_access!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
+
+/// Strips module paths from every path segment of a type name produced by `std::any::type_name`.
+pub fn short_type_name(type_name: &str) -> String {
+    let mut short = String::with_capacity(type_name.len());
+    let mut segment_start = 0;
+    for (idx, ch) in type_name.char_indices() {
+        match ch {
+            ':' => segment_start = idx + 1,
+            '<' | '>' | ',' | ' ' | '(' | ')' | '[' | ']' | ';' | '&' => {
+                short.push_str(&type_name[segment_start..idx]);
+                short.push(ch);
+                segment_start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    short.push_str(&type_name[segment_start..]);
+    short
+}
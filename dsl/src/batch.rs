@@ -0,0 +1,187 @@
+use std::cell::Cell;
+
+use specs::{
+    Accessor, AccessorCow, BatchAccessor, BatchController, BatchUncheckedWorld, Dispatcher, DispatcherBuilder,
+    RunningTime, System, SystemData, World,
+};
+
+use crate::{DslSystemData, SystemDataType};
+
+/// A system declared with `#[system(Data, batch)]`.
+///
+/// The `#[run]` method receives the declared data and returns how many times the inner dispatcher runs.
+pub trait DslBatch: for<'a> DslSystemData<'a> {
+    fn run_batch(&mut self, data: SystemDataType<'_, Self>) -> usize;
+
+    /// Registers the change readers of the data, see `ChangeTracker`.
+    fn register_readers(&mut self, _world: &mut World) {}
+
+    /// Collects the changes of the data since the previous run, see `ChangeTracker`.
+    fn collect_changes(&mut self, _data: &mut SystemDataType<'_, Self>) {}
+
+    fn running_time(&self) -> RunningTime {
+        RunningTime::VeryLong
+    }
+}
+
+/// The inner dispatcher of a DSL batch. Unlike `DispatcherBuilder` it takes no thread-local systems,
+/// since the batch runs its inner dispatcher on the threads of the outer one.
+pub struct BatchBuilder<'a, 'b> {
+    builder: DispatcherBuilder<'a, 'b>,
+}
+
+impl<'a, 'b> Default for BatchBuilder<'a, 'b> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, 'b> BatchBuilder<'a, 'b> {
+    pub fn new() -> Self {
+        Self {
+            builder: DispatcherBuilder::new(),
+        }
+    }
+
+    pub fn with<T>(mut self, system: T, name: &str, dependencies: &[&str]) -> Self
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.add(system, name, dependencies);
+        self
+    }
+
+    pub fn add<T>(&mut self, system: T, name: &str, dependencies: &[&str])
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.builder.add(system, name, dependencies);
+    }
+
+    pub fn with_barrier(mut self) -> Self {
+        self.add_barrier();
+        self
+    }
+
+    pub fn add_barrier(&mut self) {
+        self.builder.add_barrier();
+    }
+}
+
+thread_local! {
+    static BUILDING_BATCH: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Registers DSL batch systems with their inner dispatcher:
+///
+/// ```ignore
+/// DispatcherBuilder::new().with_dsl_batch::<PhysicsSubSteps>(BatchBuilder::new().with(Move, "move", &[]), "physics", &[])
+/// ```
+pub trait DslBatchExt<'a, 'b> {
+    fn with_dsl_batch<S>(self, inner: BatchBuilder<'a, 'b>, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: DslBatch + Default + Send + 'a;
+
+    fn add_dsl_batch<S>(&mut self, inner: BatchBuilder<'a, 'b>, name: &str, dependencies: &[&str])
+    where
+        S: DslBatch + Default + Send + 'a;
+}
+
+impl<'a, 'b: 'a> DslBatchExt<'a, 'b> for DispatcherBuilder<'a, 'b> {
+    fn with_dsl_batch<S>(mut self, inner: BatchBuilder<'a, 'b>, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: DslBatch + Default + Send + 'a,
+    {
+        self.add_dsl_batch::<S>(inner, name, dependencies);
+        self
+    }
+
+    fn add_dsl_batch<S>(&mut self, inner: BatchBuilder<'a, 'b>, name: &str, dependencies: &[&str])
+    where
+        S: DslBatch + Default + Send + 'a,
+    {
+        BUILDING_BATCH.with(|building| building.set(true));
+        self.add_batch::<Batch<'a, 'b, S>>(inner.builder, name, dependencies);
+    }
+}
+
+/// The batch controller of a DSL batch system, which is registered with `DslBatchExt::with_dsl_batch`.
+pub struct Batch<'a, 'b, S> {
+    accessor: BatchAccessor,
+    dispatcher: Dispatcher<'a, 'b>,
+    system: S,
+}
+
+impl<'a, 'b, S> Batch<'a, 'b, S> {
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    pub fn system_mut(&mut self) -> &mut S {
+        &mut self.system
+    }
+}
+
+impl<'a, 'b, S> BatchController<'a, 'b> for Batch<'a, 'b, S>
+where
+    S: DslBatch + Default,
+{
+    type BatchSystemData = SystemDataType<'a, S>;
+
+    unsafe fn create(accessor: BatchAccessor, dispatcher: Dispatcher<'a, 'b>) -> Self {
+        assert!(
+            BUILDING_BATCH.with(|building| building.replace(false)),
+            "The inner dispatcher of the DSL batch `{}` may have thread-local systems, \
+             register it with `DslBatchExt::with_dsl_batch` instead of `DispatcherBuilder::with_batch`",
+            std::any::type_name::<S>()
+        );
+        // `DispatcherBuilder::add_batch` takes only the reads of the controller data into account.
+        let mut writes = accessor.writes();
+        writes.extend(<Self::BatchSystemData as SystemData>::writes());
+        writes.sort();
+        writes.dedup();
+
+        Self {
+            accessor: BatchAccessor::new(accessor.reads(), writes),
+            dispatcher,
+            system: S::default(),
+        }
+    }
+}
+
+impl<'a, 'b, 'c, S> System<'c> for Batch<'a, 'b, S>
+where
+    S: DslBatch,
+{
+    type SystemData = BatchUncheckedWorld<'c>;
+
+    fn run(&mut self, data: Self::SystemData) {
+        // The controller data must be released before the inner systems fetch the same resources.
+        let runs = {
+            let mut batch_data = <SystemDataType<'c, S> as SystemData<'c>>::fetch(data.0);
+            self.system.collect_changes(&mut batch_data);
+            self.system.run_batch(batch_data)
+        };
+        for _ in 0..runs {
+            self.dispatcher.dispatch(data.0);
+        }
+    }
+
+    fn running_time(&self) -> RunningTime {
+        self.system.running_time()
+    }
+
+    fn accessor<'d>(&'d self) -> AccessorCow<'c, 'd, Self> {
+        AccessorCow::Ref(&self.accessor)
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        <SystemDataType<'_, S> as SystemData<'_>>::setup(world);
+        self.system.register_readers(world);
+        self.dispatcher.setup(world);
+    }
+}
+
+// SAFETY: The dispatcher is only `!Send` for its thread-local systems. The inner dispatcher is built from
+// a `BatchBuilder`, which takes no thread-local systems, as checked in `create`. The other inner systems are `Send`.
+unsafe impl<'a, 'b, S: Send> Send for Batch<'a, 'b, S> {}
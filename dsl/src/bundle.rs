@@ -0,0 +1,50 @@
+use specs::{Component, Entity, SystemData, World, WorldExt, WriteStorage};
+
+/// Owned components which are inserted into an entity together, e.g. `(Pos(0., 0.), Vel(1., 1.))`.
+pub trait ComponentBundle {
+    /// Registers the storages of the bundle components unless they are registered yet.
+    fn register(world: &mut World);
+
+    /// Inserts the components into the entity. The storages must be registered.
+    fn insert_into(self, world: &World, entity: Entity);
+}
+
+fn insert_component<T: Component>(world: &World, entity: Entity, component: T) {
+    world
+        .write_storage::<T>()
+        .insert(entity, component)
+        .expect("Cannot insert a component into a dead entity");
+}
+
+macro_rules! impl_component_bundle {
+    ($($ty:ident),*) => {
+        impl<$($ty: Component),*> ComponentBundle for ($($ty,)*) {
+            fn register(world: &mut World) {
+                $(<WriteStorage<$ty> as SystemData>::setup(world);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn insert_into(self, world: &World, entity: Entity) {
+                let ($($ty,)*) = self;
+                $(insert_component(world, entity, $ty);)*
+            }
+        }
+    };
+}
+
+impl_component_bundle!(A);
+impl_component_bundle!(A, B);
+impl_component_bundle!(A, B, C);
+impl_component_bundle!(A, B, C, D);
+impl_component_bundle!(A, B, C, D, E);
+impl_component_bundle!(A, B, C, D, E, F);
+impl_component_bundle!(A, B, C, D, E, F, G);
+impl_component_bundle!(A, B, C, D, E, F, G, H);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_component_bundle!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
@@ -0,0 +1,167 @@
+use std::{collections::BTreeMap, fmt::Debug};
+
+use specs::Entity;
+
+/// The cloned components of an entity, generated for data items declared with `#[data_item(capture)]`.
+///
+/// The captured components must implement `Clone`, `PartialEq` and `Debug`, so captures and their diffs
+/// can be compared and printed in test failures.
+pub trait CaptureRecord: Clone + PartialEq + Debug {
+    /// The names of the fields which differ from the other record, in declaration order.
+    fn changed_fields(&self, other: &Self) -> Vec<&'static str>;
+}
+
+/// Captured components of a data item by entity, e.g. `PosChange::capture(&world)`:
+///
+/// ```ignore
+/// let before = PosChange::capture(&world);
+/// dispatcher.dispatch(&world);
+/// let diff = before.diff(&PosChange::capture(&world));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture<R> {
+    pub items: BTreeMap<Entity, R>,
+}
+
+impl<R> Default for Capture<R> {
+    fn default() -> Self {
+        Self { items: BTreeMap::new() }
+    }
+}
+
+impl<R: CaptureRecord> Capture<R> {
+    pub fn get(&self, entity: Entity) -> Option<&R> {
+        self.items.get(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Compares the capture with a later one. The entities of all lists are in ascending order.
+    ///
+    /// An entity recreated with the same index is a different entity, so it is both removed and added.
+    pub fn diff(&self, next: &Self) -> CaptureDiff<R> {
+        let mut diff = CaptureDiff {
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+        for (&entity, old) in &self.items {
+            match next.items.get(&entity) {
+                Some(new) if old != new => diff.changed.push(ChangedItem {
+                    entity,
+                    fields: old.changed_fields(new),
+                    old: old.clone(),
+                    new: new.clone(),
+                }),
+                Some(_) => {}
+                None => diff.removed.push((entity, old.clone())),
+            }
+        }
+        for (&entity, new) in &next.items {
+            if !self.items.contains_key(&entity) {
+                diff.added.push((entity, new.clone()));
+            }
+        }
+        diff
+    }
+}
+
+/// The difference between two captures of a data item, see `Capture::diff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CaptureDiff<R> {
+    pub added: Vec<(Entity, R)>,
+    pub removed: Vec<(Entity, R)>,
+    pub changed: Vec<ChangedItem<R>>,
+}
+
+impl<R> CaptureDiff<R> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// An entity matching the item in both captures with different components.
+/// The old and new values of a changed field are in the records under the field name.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedItem<R> {
+    pub entity: Entity,
+    pub fields: Vec<&'static str>,
+    pub old: R,
+    pub new: R,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, World, WorldExt};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct PosCapture {
+        position: (i32, i32),
+        velocity: (i32, i32),
+    }
+
+    impl CaptureRecord for PosCapture {
+        fn changed_fields(&self, other: &Self) -> Vec<&'static str> {
+            let mut fields = vec![];
+            if self.position != other.position {
+                fields.push("position");
+            }
+            if self.velocity != other.velocity {
+                fields.push("velocity");
+            }
+            fields
+        }
+    }
+
+    #[test]
+    fn test_capture_diff() {
+        let mut world = World::new();
+        let moved = world.create_entity().build();
+        let still = world.create_entity().build();
+        let killed = world.create_entity().build();
+        world.delete_entity(killed).unwrap();
+        let spawned = world.create_entity().build();
+        assert_eq!(spawned.id(), killed.id());
+
+        let record = |position, velocity| PosCapture { position, velocity };
+        let before = Capture {
+            items: vec![
+                (moved, record((0, 0), (1, 0))),
+                (still, record((5, 5), (0, 0))),
+                (killed, record((9, 9), (0, 0))),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let after = Capture {
+            items: vec![
+                (moved, record((1, 0), (1, 0))),
+                (still, record((5, 5), (0, 0))),
+                (spawned, record((0, 0), (0, 0))),
+            ]
+            .into_iter()
+            .collect(),
+        };
+
+        let diff = before.diff(&after);
+        assert_eq!(diff.added, vec![(spawned, record((0, 0), (0, 0)))]);
+        assert_eq!(diff.removed, vec![(killed, record((9, 9), (0, 0)))]);
+        assert_eq!(
+            diff.changed,
+            vec![ChangedItem {
+                entity: moved,
+                fields: vec!["position"],
+                old: record((0, 0), (1, 0)),
+                new: record((1, 0), (1, 0)),
+            }]
+        );
+        assert!(after.diff(&after).is_empty());
+    }
+}
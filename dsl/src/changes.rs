@@ -0,0 +1,626 @@
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    slice,
+    sync::{Arc, Mutex},
+};
+
+use specs::{
+    hibitset::{BitSet, BitSetAnd},
+    join::Join,
+    shred::{Resource, ResourceId},
+    storage::{ComponentEvent, MaskedStorage},
+    world::Index,
+    Component, Entities, Entity, ParJoin, Read, ReadStorage, ReaderId, Storage, SystemData, Tracked, World, WorldExt,
+    Write,
+};
+
+use crate::{Access, FieldKind, SystemAccess};
+
+/// Hooks of system data which track component changes on behalf of a system.
+///
+/// Only systems declared with a change tracker, e.g. `#[system(Data, changes = tracker)]`, call the hooks,
+/// so the data of other systems doesn't need to implement the trait.
+pub trait TrackedData {
+    /// The number of change readers of the data, which is non-zero if the data has change filters
+    /// joining only the changes since the previous system run.
+    const READERS: usize = 0;
+
+    /// Registers the change readers of the data with the tracker of the system. Called from the system setup.
+    fn register_readers(_world: &mut World, _tracker: &mut ChangeTracker) {}
+
+    /// Collects the changes since the previous run of the system. Called before the `#[run]` method.
+    fn collect_changes(&mut self, _readers: &mut ChangeReaders<'_>) {}
+}
+
+/// The change readers of a system instance, which are kept in a field of the system:
+///
+/// ```ignore
+/// #[derive(Default)]
+/// struct Follow {
+///     changes: ChangeTracker,
+/// }
+///
+/// #[system(FollowData, changes = changes)]
+/// impl Follow { ... }
+/// ```
+///
+/// Every change filter and component hook of the instance owns a reader, so neither instances of the same
+/// system nor filters of the same component steal each other's events.
+#[derive(Default)]
+pub struct ChangeTracker {
+    readers: Vec<SharedReader>,
+}
+
+/// The reader is shared with the `Changed` storage, which skips the system's own changes on drop.
+pub(crate) type SharedReader = Arc<Mutex<ChangeReader>>;
+
+pub(crate) struct ChangeReader {
+    pub(crate) events: ReaderId<ComponentEvent>,
+    /// The entities having the component, tracked by removal hooks, see `ChangeTracker::track_owners`.
+    pub(crate) owners: BTreeMap<Index, Entity>,
+}
+
+impl ChangeTracker {
+    /// Registers a reader of the component events of `T`.
+    pub fn register<T: Component>(&mut self, world: &mut World)
+    where
+        T::Storage: Tracked,
+    {
+        let events = world.write_storage::<T>().register_reader();
+        self.readers.push(Arc::new(Mutex::new(ChangeReader {
+            events,
+            owners: BTreeMap::new(),
+        })));
+    }
+
+    /// Records the entities having `T` with the last registered reader, which keeps them up to date,
+    /// so removals are reported with the entity which had the component even if its index is reused.
+    pub(crate) fn track_owners<T: Component>(&mut self, world: &World) {
+        let (entities, storage) = world.system_data::<(Entities, ReadStorage<T>)>();
+        let reader = self
+            .readers
+            .last()
+            .expect("The reader of the owners must be registered first");
+        reader.lock().expect("The change reader is poisoned").owners = (&entities, storage.mask())
+            .join()
+            .map(|(entity, _)| (entity.id(), entity))
+            .collect();
+    }
+
+    /// Drops the registered readers, e.g. before the system is set up again.
+    pub fn clear(&mut self) {
+        self.readers.clear();
+    }
+
+    /// The readers in registration order, which are taken by `TrackedData::collect_changes`.
+    pub fn readers(&self) -> ChangeReaders<'_> {
+        ChangeReaders {
+            readers: self.readers.iter(),
+        }
+    }
+}
+
+/// The change readers of a system run, see `ChangeTracker::readers`.
+pub struct ChangeReaders<'t> {
+    readers: slice::Iter<'t, SharedReader>,
+}
+
+impl<'t> ChangeReaders<'t> {
+    pub(crate) fn next<T>(&mut self) -> SharedReader {
+        let reader = self.readers.next().unwrap_or_else(|| {
+            panic!(
+                "The change reader of `{}` is not registered, the system must be set up first",
+                std::any::type_name::<T>()
+            )
+        });
+        reader.clone()
+    }
+
+    /// Skips the readers of data which is absent in this run, e.g. of an `Option`.
+    pub fn skip(&mut self, count: usize) {
+        for _ in 0..count {
+            self.readers.next();
+        }
+    }
+}
+
+/// Collects indices of the events matching the filter `F` into the mask.
+pub(crate) fn read_changes<T, D, F>(storage: &Storage<T, D>, reader: &SharedReader, mask: &mut BitSet)
+where
+    T: Component,
+    T::Storage: Tracked,
+    D: Deref<Target = MaskedStorage<T>>,
+    F: ChangeFilter,
+{
+    let mut reader = reader.lock().expect("The change reader is poisoned");
+    mask.clear();
+    for event in storage.channel().read(&mut reader.events) {
+        if let Some(idx) = F::index(event) {
+            mask.add(idx);
+        }
+    }
+}
+
+fn assert_collected<T>(collected: bool) {
+    if !collected {
+        panic!(
+            "The changes of `{}` are not collected, the system must declare a change tracker, \
+             e.g. `#[system(Data, changes = tracker)]`",
+            std::any::type_name::<T>()
+        );
+    }
+}
+
+/// A change event filter of the `Changed` storage.
+pub trait ChangeFilter {
+    fn index(event: &ComponentEvent) -> Option<Index>;
+}
+
+/// Matches components inserted since the previous run.
+pub struct Inserted;
+
+/// Matches components modified since the previous run.
+pub struct Modified;
+
+impl ChangeFilter for Inserted {
+    fn index(event: &ComponentEvent) -> Option<Index> {
+        match event {
+            ComponentEvent::Inserted(idx) => Some(*idx),
+            _ => None,
+        }
+    }
+}
+
+impl ChangeFilter for Modified {
+    fn index(event: &ComponentEvent) -> Option<Index> {
+        match event {
+            ComponentEvent::Modified(idx) => Some(*idx),
+            _ => None,
+        }
+    }
+}
+
+/// Matches components removed since the previous run.
+pub struct Removal;
+
+impl ChangeFilter for Removal {
+    fn index(event: &ComponentEvent) -> Option<Index> {
+        match event {
+            ComponentEvent::Removed(idx) => Some(*idx),
+            _ => None,
+        }
+    }
+}
+
+impl<A: ChangeFilter, B: ChangeFilter> ChangeFilter for (A, B) {
+    fn index(event: &ComponentEvent) -> Option<Index> {
+        A::index(event).or_else(|| B::index(event))
+    }
+}
+
+/// Resolves the component type of a storage.
+pub trait ComponentStorage {
+    type Component: Component;
+}
+
+impl<'e, T: Component, D> ComponentStorage for Storage<'e, T, D> {
+    type Component = T;
+}
+
+/// A `FlaggedStorage` which joins only components matching the change filter `F`,
+/// e.g. `Changed<'a, ReadStorage<'a, Pos>, Modified>`.
+///
+/// Changes made by the system itself, e.g. by mutably joining over the storage, are skipped on drop,
+/// so its next run joins only components changed elsewhere.
+pub struct Changed<'a, S: ComponentStorage, F> {
+    storage: S,
+    mask: BitSet,
+    collected: bool,
+    skip_own: Option<(SharedReader, SkipChanges<S>)>,
+    marker: PhantomData<(&'a (), F)>,
+}
+
+type SkipChanges<S> = fn(&S, &mut ReaderId<ComponentEvent>);
+
+impl<'a, S: ComponentStorage, F> Drop for Changed<'a, S, F> {
+    fn drop(&mut self) {
+        if let Some((reader, skip)) = self.skip_own.take() {
+            // It runs on drop, so it must not panic again while a panicking system unwinds.
+            if let Ok(mut reader) = reader.lock() {
+                skip(&self.storage, &mut reader.events);
+            }
+        }
+    }
+}
+
+impl<'a, S: ComponentStorage, F> Changed<'a, S, F> {
+    /// Entities matching the change filter since the previous run.
+    pub fn mask(&self) -> &BitSet {
+        assert_collected::<S::Component>(self.collected);
+        &self.mask
+    }
+}
+
+impl<'a, S: ComponentStorage, F> Deref for Changed<'a, S, F> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.storage
+    }
+}
+
+impl<'a, S: ComponentStorage, F> DerefMut for Changed<'a, S, F> {
+    fn deref_mut(&mut self) -> &mut S {
+        &mut self.storage
+    }
+}
+
+impl<'a, S, F> SystemData<'a> for Changed<'a, S, F>
+where
+    S: ComponentStorage + SystemData<'a>,
+{
+    fn setup(world: &mut World) {
+        S::setup(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Self {
+            storage: S::fetch(world),
+            mask: BitSet::new(),
+            collected: false,
+            skip_own: None,
+            marker: PhantomData,
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        S::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        S::writes()
+    }
+}
+
+impl<'a, S: ComponentStorage + SystemAccess, F> SystemAccess for Changed<'a, S, F> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        S::collect_accesses(accesses)
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        S::collect_missing(world, missing);
+    }
+}
+
+impl<'a, 'e, T, D, F> TrackedData for Changed<'a, Storage<'e, T, D>, F>
+where
+    T: Component,
+    T::Storage: Tracked,
+    D: Deref<Target = MaskedStorage<T>>,
+    F: ChangeFilter,
+{
+    const READERS: usize = 1;
+
+    fn register_readers(world: &mut World, tracker: &mut ChangeTracker) {
+        tracker.register::<T>(world);
+    }
+
+    fn collect_changes(&mut self, readers: &mut ChangeReaders<'_>) {
+        let reader = readers.next::<T>();
+        read_changes::<T, D, F>(&self.storage, &reader, &mut self.mask);
+        self.collected = true;
+        self.skip_own = Some((reader, |storage, reader| storage.channel().read(reader).for_each(drop)));
+    }
+}
+
+impl<'j, 'a, 'e, T, D, F> Join for &'j Changed<'a, Storage<'e, T, D>, F>
+where
+    T: Component,
+    D: Deref<Target = MaskedStorage<T>>,
+{
+    type Mask = BitSetAnd<&'j BitSet, &'j BitSet>;
+    type Type = &'j T;
+    type Value = &'j T::Storage;
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        assert_collected::<T>(self.collected);
+        let (mask, value) = (&self.storage).open();
+        (BitSetAnd(mask, &self.mask), value)
+    }
+
+    unsafe fn get(value: &mut Self::Value, idx: Index) -> Self::Type {
+        <&'j Storage<'e, T, D> as Join>::get(value, idx)
+    }
+}
+
+impl<'j, 'a, 'e, T, D, F> Join for &'j mut Changed<'a, Storage<'e, T, D>, F>
+where
+    T: Component,
+    D: DerefMut<Target = MaskedStorage<T>>,
+{
+    type Mask = BitSetAnd<&'j BitSet, &'j BitSet>;
+    type Type = &'j mut T;
+    type Value = &'j mut T::Storage;
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        assert_collected::<T>(self.collected);
+        let Changed { storage, mask, .. } = self;
+        let (storage_mask, value) = storage.open();
+        (BitSetAnd(storage_mask, mask), value)
+    }
+
+    unsafe fn get(value: &mut Self::Value, idx: Index) -> Self::Type {
+        <&'j mut Storage<'e, T, D> as Join>::get(value, idx)
+    }
+}
+
+// SAFETY: The change mask only restricts the storage join, which is `ParJoin` itself.
+unsafe impl<'j, 'a, 'e, T, D, F> ParJoin for &'j Changed<'a, Storage<'e, T, D>, F>
+where
+    T: Component,
+    D: Deref<Target = MaskedStorage<T>>,
+    &'j Storage<'e, T, D>: ParJoin,
+{
+}
+
+// SAFETY: The change mask only restricts the storage join, which is `ParJoin` itself.
+unsafe impl<'j, 'a, 'e, T, D, F> ParJoin for &'j mut Changed<'a, Storage<'e, T, D>, F>
+where
+    T: Component,
+    D: DerefMut<Target = MaskedStorage<T>>,
+    &'j mut Storage<'e, T, D>: ParJoin,
+{
+}
+
+/// Joins entities whose component `T` was removed since the previous run.
+pub struct Removed<'a, T: Component> {
+    storage: ReadStorage<'a, T>,
+    mask: BitSet,
+    collected: bool,
+}
+
+impl<'a, T: Component> Removed<'a, T> {
+    pub fn mask(&self) -> &BitSet {
+        assert_collected::<T>(self.collected);
+        &self.mask
+    }
+}
+
+impl<'a, T: Component> SystemData<'a> for Removed<'a, T> {
+    fn setup(world: &mut World) {
+        <ReadStorage<'a, T> as SystemData<'a>>::setup(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Self {
+            storage: SystemData::fetch(world),
+            mask: BitSet::new(),
+            collected: false,
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        <ReadStorage<'a, T> as SystemData<'a>>::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+}
+
+impl<'a, T: Component> SystemAccess for Removed<'a, T> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access::new::<T>(FieldKind::Component));
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        <ReadStorage<T> as SystemAccess>::collect_missing(world, missing);
+    }
+}
+
+impl<'a, T: Component> TrackedData for Removed<'a, T>
+where
+    T::Storage: Tracked,
+{
+    const READERS: usize = 1;
+
+    fn register_readers(world: &mut World, tracker: &mut ChangeTracker) {
+        tracker.register::<T>(world);
+    }
+
+    fn collect_changes(&mut self, readers: &mut ChangeReaders<'_>) {
+        read_changes::<T, _, Removal>(&self.storage, &readers.next::<T>(), &mut self.mask);
+        self.collected = true;
+    }
+}
+
+impl<'j, 'a, T: Component> Join for &'j Removed<'a, T> {
+    type Mask = &'j BitSet;
+    type Type = ();
+    type Value = ();
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        (self.mask(), ())
+    }
+
+    unsafe fn get(_value: &mut Self::Value, _idx: Index) -> Self::Type {}
+}
+
+// SAFETY: The join yields no data.
+unsafe impl<'j, 'a, T: Component> ParJoin for &'j Removed<'a, T> {}
+
+impl TrackedData for () {}
+
+impl<T: TrackedData> TrackedData for Option<T> {
+    const READERS: usize = T::READERS;
+
+    fn register_readers(world: &mut World, tracker: &mut ChangeTracker) {
+        T::register_readers(world, tracker)
+    }
+
+    fn collect_changes(&mut self, readers: &mut ChangeReaders<'_>) {
+        match self {
+            Some(data) => data.collect_changes(readers),
+            None => readers.skip(T::READERS),
+        }
+    }
+}
+
+impl<'a, T: Resource, F> TrackedData for Read<'a, T, F> {}
+
+impl<'a, T: Resource, F> TrackedData for Write<'a, T, F> {}
+
+impl<'a, T: Component, D> TrackedData for Storage<'a, T, D> {}
+
+macro_rules! impl_tracked_data {
+    ($($ty:ident),*) => {
+        impl<$($ty: TrackedData),*> TrackedData for ($($ty,)*) {
+            const READERS: usize = 0 $(+ $ty::READERS)*;
+
+            fn register_readers(world: &mut World, tracker: &mut ChangeTracker) {
+                $($ty::register_readers(world, tracker);)*
+            }
+
+            #[allow(non_snake_case)]
+            fn collect_changes(&mut self, readers: &mut ChangeReaders<'_>) {
+                let ($($ty,)*) = self;
+                $($ty.collect_changes(readers);)*
+            }
+        }
+    };
+}
+
+impl_tracked_data!(A);
+impl_tracked_data!(A, B);
+impl_tracked_data!(A, B, C);
+impl_tracked_data!(A, B, C, D);
+impl_tracked_data!(A, B, C, D, E);
+impl_tracked_data!(A, B, C, D, E, F);
+impl_tracked_data!(A, B, C, D, E, F, G);
+impl_tracked_data!(A, B, C, D, E, F, G, H);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y);
+impl_tracked_data!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, Entities, FlaggedStorage, VecStorage, WriteStorage};
+
+    struct Pos(u32);
+
+    impl Component for Pos {
+        type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+    }
+
+    type Data<'a> = (
+        Entities<'a>,
+        Changed<'a, ReadStorage<'a, Pos>, (Inserted, Modified)>,
+        Removed<'a, Pos>,
+    );
+
+    fn changes(world: &World, tracker: &ChangeTracker) -> (Vec<u32>, Vec<Index>) {
+        let mut data = <Data as SystemData>::fetch(world);
+        data.collect_changes(&mut tracker.readers());
+
+        let (entities, changed, removed) = &data;
+        let changed = (entities, changed).join().map(|(_, pos)| pos.0).collect();
+        let removed = (entities, removed).join().map(|(entity, _)| entity.id()).collect();
+        (changed, removed)
+    }
+
+    #[test]
+    fn test_change_filters() {
+        let mut world = World::new();
+        let mut tracker = ChangeTracker::default();
+        <Data as SystemData>::setup(&mut world);
+        <Data as TrackedData>::register_readers(&mut world, &mut tracker);
+
+        let first = world.create_entity().with(Pos(1)).build();
+        let second = world.create_entity().with(Pos(2)).build();
+        assert_eq!(changes(&world, &tracker), (vec![1, 2], vec![]));
+        assert_eq!(changes(&world, &tracker), (vec![], vec![]));
+
+        {
+            let mut positions: WriteStorage<Pos> = SystemData::fetch(&world);
+            positions.get_mut(second).unwrap().0 = 3;
+            positions.remove(first);
+        }
+        assert_eq!(changes(&world, &tracker), (vec![3], vec![first.id()]));
+        assert_eq!(changes(&world, &tracker), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_filters_of_the_same_component() {
+        type PairData<'a> = (
+            Changed<'a, ReadStorage<'a, Pos>, Inserted>,
+            Changed<'a, ReadStorage<'a, Pos>, Inserted>,
+        );
+
+        let mut world = World::new();
+        let mut tracker = ChangeTracker::default();
+        <PairData as SystemData>::setup(&mut world);
+        <PairData as TrackedData>::register_readers(&mut world, &mut tracker);
+        world.create_entity().with(Pos(1)).build();
+
+        let mut data = <PairData as SystemData>::fetch(&world);
+        data.collect_changes(&mut tracker.readers());
+        assert_eq!((&data.0).join().count(), 1);
+        assert_eq!((&data.1).join().count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "the system must declare a change tracker")]
+    fn test_uncollected_changes() {
+        let mut world = World::new();
+        <Data as SystemData>::setup(&mut world);
+        let (entities, changed, _) = <Data as SystemData>::fetch(&world);
+        (&entities, &changed).join().count();
+    }
+
+    #[test]
+    fn test_skip_own_changes() {
+        type MutData<'a> = Changed<'a, WriteStorage<'a, Pos>, Modified>;
+
+        fn run(world: &World, tracker: &ChangeTracker) -> usize {
+            let mut data = <MutData as SystemData>::fetch(world);
+            data.collect_changes(&mut tracker.readers());
+            let mut count = 0;
+            for pos in (&mut data).join() {
+                pos.0 += 1;
+                count += 1;
+            }
+            count
+        }
+
+        let mut world = World::new();
+        let mut tracker = ChangeTracker::default();
+        <MutData as SystemData>::setup(&mut world);
+        <MutData as TrackedData>::register_readers(&mut world, &mut tracker);
+        let first = world.create_entity().with(Pos(1)).build();
+        world.create_entity().with(Pos(2)).build();
+
+        assert_eq!(run(&world, &tracker), 0);
+        world.write_storage::<Pos>().get_mut(first).unwrap().0 = 5;
+        assert_eq!(run(&world, &tracker), 1);
+        assert_eq!(run(&world, &tracker), 0);
+        assert_eq!(world.read_storage::<Pos>().get(first).unwrap().0, 6);
+    }
+}
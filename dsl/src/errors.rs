@@ -0,0 +1,71 @@
+use std::{error::Error, fmt, mem, sync::Mutex};
+
+/// An error returned by the `#[run]` method of a system, tagged with the system name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemError {
+    pub system: &'static str,
+    pub message: String,
+}
+
+impl SystemError {
+    pub fn new(system: &'static str, error: &impl fmt::Display) -> Self {
+        Self {
+            system,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for SystemError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The system `{}` failed: {}", self.system, self.message)
+    }
+}
+
+impl Error for SystemError {}
+
+/// Errors of systems with the default `on_error = "collect"` policy, in the order they were returned.
+///
+/// Systems push errors through a shared reference, so fallible systems still run in parallel.
+#[derive(Debug, Default)]
+pub struct SystemErrors {
+    errors: Mutex<Vec<SystemError>>,
+}
+
+impl SystemErrors {
+    pub fn push(&self, error: SystemError) {
+        self.errors
+            .lock()
+            .expect("The system errors lock is poisoned")
+            .push(error);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors
+            .lock()
+            .expect("The system errors lock is poisoned")
+            .is_empty()
+    }
+
+    /// Takes the collected errors, e.g. after each dispatch.
+    pub fn take(&mut self) -> Vec<SystemError> {
+        mem::take(self.errors.get_mut().expect("The system errors lock is poisoned"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_errors() {
+        let mut errors = SystemErrors::default();
+        errors.push(SystemError::new("PhysicsSystem", &"no ground"));
+        assert!(!errors.is_empty());
+
+        let taken = errors.take();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(taken[0].to_string(), "The system `PhysicsSystem` failed: no ground");
+        assert!(errors.is_empty());
+    }
+}
@@ -0,0 +1,32 @@
+use specs::shrev::{Event, EventChannel, EventIterator};
+
+/// Events read by a system declared with `#[system(Data, reads(E))]`, which are received since its previous run.
+pub type Events<'a, E> = EventIterator<'a, E>;
+
+/// Writes events of a system declared with `#[system(Data, emits(E))]`.
+pub struct Emitter<'a, E: Event> {
+    channel: &'a mut EventChannel<E>,
+}
+
+impl<'a, E: Event> Emitter<'a, E> {
+    pub fn new(channel: &'a mut EventChannel<E>) -> Self {
+        Self { channel }
+    }
+
+    pub fn emit(&mut self, event: E) {
+        self.channel.single_write(event);
+    }
+
+    pub fn emit_all<I>(&mut self, events: I)
+    where
+        I: IntoIterator<Item = E>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        self.channel.iter_write(events);
+    }
+
+    /// Emits all events of the vector, leaving it empty. Handy for events collected by a parallel join.
+    pub fn drain(&mut self, events: &mut Vec<E>) {
+        self.channel.drain_vec_write(events);
+    }
+}
@@ -0,0 +1,113 @@
+use std::{error::Error, fmt};
+
+use specs::{SystemData, World};
+
+use crate::{short_type_name, Access, DslSystem, DslSystemData};
+
+/// The storages and resources of a system which are absent from the world, so fetching its data would panic.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FetchError {
+    pub system: &'static str,
+    pub missing: Vec<Access>,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The system `{}` cannot fetch its data: missing", self.system)?;
+        for (idx, access) in self.missing.iter().enumerate() {
+            let kind = if access.kind.is_component() {
+                "storage"
+            } else {
+                "resource"
+            };
+            let separator = if idx == 0 { " " } else { ", " };
+            write!(f, "{}{} `{}`", separator, kind, short_type_name(access.type_name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for FetchError {}
+
+/// Checks that the world contains everything the system fetches, including its event channels and hooks.
+///
+/// Change readers and the system state are added by the system setup, so validate the world after `setup`.
+pub fn validate<S: DslSystem>(world: &World) -> Result<(), FetchError> {
+    let mut missing: Vec<Access> = vec![];
+    for access in S::missing(world) {
+        if !missing.contains(&access) {
+            missing.push(access);
+        }
+    }
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(FetchError {
+            system: S::NAME,
+            missing,
+        })
+    }
+}
+
+/// Fetches the system data declared in the `#[system]` attribute, or returns which parts of it are missing.
+pub fn try_fetch<'a, S>(world: &'a World) -> Result<<S as DslSystemData<'a>>::Data, FetchError>
+where
+    S: DslSystem + DslSystemData<'a>,
+{
+    validate::<S>(world)?;
+    Ok(SystemData::fetch(world))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Component, Read, ReadExpect, VecStorage, WorldExt, WriteStorage};
+
+    use crate::{SystemAccess, SystemGraph};
+
+    struct Pos;
+    struct DeltaTime;
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    struct Physics;
+
+    type PhysicsData<'a> = (WriteStorage<'a, Pos>, ReadExpect<'a, DeltaTime>, Option<Read<'a, u32>>);
+
+    impl DslSystem for Physics {
+        const NAME: &'static str = "Physics";
+
+        fn accesses() -> Vec<Access> {
+            <PhysicsData as SystemAccess>::accesses()
+        }
+
+        fn missing(world: &World) -> Vec<Access> {
+            <PhysicsData as SystemAccess>::missing(world)
+        }
+    }
+
+    impl<'a> DslSystemData<'a> for Physics {
+        type Data = PhysicsData<'a>;
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut world = World::new();
+        let graph = SystemGraph::new().with::<Physics>("physics", &[]);
+        let error = validate::<Physics>(&world).unwrap_err();
+        assert_eq!(error.missing, Physics::accesses()[..2].to_vec());
+        assert_eq!(
+            error.to_string(),
+            "The system `Physics` cannot fetch its data: missing storage `Pos`, resource `DeltaTime`"
+        );
+        assert_eq!(graph.validate(&world), Err(vec![error]));
+
+        world.register::<Pos>();
+        world.insert(DeltaTime);
+        assert!(graph.validate(&world).is_ok());
+        let (_, _, count) = try_fetch::<Physics>(&world).unwrap();
+        assert!(count.is_none());
+    }
+}
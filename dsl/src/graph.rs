@@ -0,0 +1,531 @@
+use std::{any::type_name, collections::BTreeSet, fmt::Write};
+
+use specs::World;
+
+use crate::{json, short_type_name, Access, DslSystem, FetchError, FieldKind};
+
+/// A system registered in the graph under its dispatcher name.
+#[derive(Clone, Debug)]
+pub struct SystemNode {
+    pub name: String,
+    pub system_type: &'static str,
+    pub thread_local: bool,
+    pub dependencies: Vec<String>,
+    pub accesses: Vec<Access>,
+}
+
+impl SystemNode {
+    pub fn reads(&self) -> impl Iterator<Item = &Access> {
+        self.accesses.iter().filter(|access| !access.kind.is_mut())
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = &Access> {
+        self.accesses.iter().filter(|access| access.kind.is_mut())
+    }
+}
+
+/// Two systems which would run in parallel but for the listed storages or resources.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict<'a> {
+    pub first: &'a str,
+    pub second: &'a str,
+    pub targets: Vec<&'static str>,
+}
+
+type Validator = fn(&World) -> Result<(), FetchError>;
+
+/// The access graph of DSL systems, which can be exported as Graphviz DOT or a JSON manifest.
+///
+/// Systems are added with the same names and dependencies as in the `DispatcherBuilder`,
+/// and must be declared with `#[system(Data, graph)]`.
+#[derive(Clone, Debug, Default)]
+pub struct SystemGraph {
+    nodes: Vec<SystemNode>,
+    validators: Vec<Validator>,
+}
+
+impl SystemGraph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with<S: DslSystem>(mut self, name: &str, dependencies: &[&str]) -> Self {
+        self.add::<S>(name, dependencies);
+        self
+    }
+
+    /// Adds a thread-local system, which runs after all parallel systems in the order of registration.
+    pub fn with_thread_local<S: DslSystem>(mut self, name: &str) -> Self {
+        self.add_thread_local::<S>(name);
+        self
+    }
+
+    /// Adds a batch system, which accesses everything its inner systems access.
+    pub fn with_batch<S: DslSystem>(mut self, inner: &SystemGraph, name: &str, dependencies: &[&str]) -> Self {
+        self.add_batch::<S>(inner, name, dependencies);
+        self
+    }
+
+    pub fn add<S: DslSystem>(&mut self, name: &str, dependencies: &[&str]) {
+        self.push::<S>(name, S::THREAD_LOCAL, dependencies);
+    }
+
+    pub fn add_thread_local<S: DslSystem>(&mut self, name: &str) {
+        self.push::<S>(name, true, &[]);
+    }
+
+    pub fn add_batch<S: DslSystem>(&mut self, inner: &SystemGraph, name: &str, dependencies: &[&str]) {
+        self.push::<S>(name, false, dependencies);
+        let node = self.nodes.last_mut().expect("The batch system node must be added");
+        node.accesses.extend(
+            inner
+                .nodes
+                .iter()
+                .flat_map(|inner_node| inner_node.accesses.iter().cloned()),
+        );
+        node.accesses.sort();
+        node.accesses.dedup();
+        self.validators.extend(inner.validators.iter().cloned());
+    }
+
+    fn push<S: DslSystem>(&mut self, name: &str, thread_local: bool, dependencies: &[&str]) {
+        let mut accesses = S::accesses();
+        accesses.sort();
+        accesses.dedup();
+
+        self.nodes.push(SystemNode {
+            name: name.to_string(),
+            system_type: type_name::<S>(),
+            thread_local,
+            dependencies: dependencies.iter().map(|dep| dep.to_string()).collect(),
+            accesses,
+        });
+        self.validators.push(crate::validate::<S>);
+    }
+
+    /// Checks the system data of all systems, including the inner systems of batches.
+    ///
+    /// Run it as a pre-flight check after `Dispatcher::setup` and before the first dispatch,
+    /// since specs panics without naming the system if any data is missing.
+    pub fn validate(&self, world: &World) -> Result<(), Vec<FetchError>> {
+        let errors: Vec<_> = self
+            .validators
+            .iter()
+            .filter_map(|validate| validate(world).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn systems(&self) -> &[SystemNode] {
+        &self.nodes
+    }
+
+    pub fn components(&self) -> BTreeSet<&'static str> {
+        self.targets(|kind| kind.is_component())
+    }
+
+    /// All accessed resources, including the entities resource.
+    pub fn resources(&self) -> BTreeSet<&'static str> {
+        self.targets(|kind| !kind.is_component())
+    }
+
+    /// Explicit dependency edges as `(dependency, dependent)` pairs.
+    pub fn dependencies(&self) -> Vec<(&str, &str)> {
+        self.nodes
+            .iter()
+            .flat_map(|node| {
+                node.dependencies
+                    .iter()
+                    .map(move |dep| (dep.as_str(), node.name.as_str()))
+            })
+            .collect()
+    }
+
+    /// Pairs of parallel systems with conflicting accesses. Thread-local systems never run in parallel,
+    /// and neither do systems ordered by dependencies, directly or transitively.
+    pub fn conflicts(&self) -> Vec<Conflict<'_>> {
+        let mut conflicts = vec![];
+        let parallel: Vec<_> = self.nodes.iter().filter(|node| !node.thread_local).collect();
+        for (idx, first) in parallel.iter().enumerate() {
+            for second in &parallel[idx + 1..] {
+                if self.is_ordered(&first.name, &second.name) {
+                    continue;
+                }
+
+                let mut targets: Vec<_> = first
+                    .accesses
+                    .iter()
+                    .filter(|access| second.accesses.iter().any(|other| access.conflicts_with(other)))
+                    .map(|access| access.type_name)
+                    .collect();
+                // The accesses are sorted by kind first, so reads and writes of a type aren't adjacent.
+                targets.sort();
+                targets.dedup();
+
+                if !targets.is_empty() {
+                    conflicts.push(Conflict {
+                        first: &first.name,
+                        second: &second.name,
+                        targets,
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Returns `true` if either system depends on the other, directly or transitively.
+    pub fn is_ordered(&self, first: &str, second: &str) -> bool {
+        self.depends_on(first, second) || self.depends_on(second, first)
+    }
+
+    fn depends_on(&self, dependent: &str, dependency: &str) -> bool {
+        let mut visited = BTreeSet::new();
+        let mut pending = vec![dependent];
+        while let Some(name) = pending.pop() {
+            if !visited.insert(name) {
+                continue;
+            }
+            for node in self.nodes.iter().filter(|node| node.name == name) {
+                for dep in &node.dependencies {
+                    if dep == dependency {
+                        return true;
+                    }
+                    pending.push(dep);
+                }
+            }
+        }
+        false
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph systems {\n");
+        out.push_str("    rankdir=LR;\n");
+
+        out.push('\n');
+        for node in &self.nodes {
+            let label = format!("{}\n({})", node.name, short_type_name(node.system_type));
+            let _ = writeln!(
+                out,
+                "    {} [shape=box, label={}];",
+                system_id(&node.name),
+                dot_str(&label)
+            );
+        }
+        for component in self.components() {
+            let id = target_id(FieldKind::Component, component);
+            let label = dot_str(&short_type_name(component));
+            let _ = writeln!(out, "    {} [shape=ellipse, label={}];", id, label);
+        }
+        for resource in self.resources() {
+            let id = target_id(FieldKind::Resource, resource);
+            let label = dot_str(&short_type_name(resource));
+            let _ = writeln!(out, "    {} [shape=note, label={}];", id, label);
+        }
+
+        out.push('\n');
+        for node in &self.nodes {
+            let system = system_id(&node.name);
+            for access in node.reads() {
+                let target = target_id(access.kind, access.type_name);
+                let _ = writeln!(out, "    {} -> {} [label=\"read\"];", target, system);
+            }
+            for access in node.writes() {
+                let target = target_id(access.kind, access.type_name);
+                let _ = writeln!(out, "    {} -> {} [label=\"write\"];", system, target);
+            }
+        }
+
+        out.push('\n');
+        for (dependency, dependent) in self.dependencies() {
+            let _ = writeln!(
+                out,
+                "    {} -> {} [style=bold, label=\"dependency\"];",
+                system_id(dependency),
+                system_id(dependent)
+            );
+        }
+        for conflict in self.conflicts() {
+            let targets: Vec<_> = conflict.targets.iter().map(|target| short_type_name(target)).collect();
+            let _ = writeln!(
+                out,
+                "    {} -> {} [dir=none, style=dashed, color=red, label={}];",
+                system_id(conflict.first),
+                system_id(conflict.second),
+                dot_str(&format!("conflict: {}", targets.join(", ")))
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        out.push_str("{\n  \"systems\": [");
+        for (idx, node) in self.nodes.iter().enumerate() {
+            out.push_str(if idx == 0 { "\n" } else { ",\n" });
+            out.push_str("    {\n      \"name\": ");
+            json::write_str(&mut out, &node.name);
+            out.push_str(",\n      \"type\": ");
+            json::write_str(&mut out, node.system_type);
+            let _ = write!(out, ",\n      \"thread_local\": {}", node.thread_local);
+            out.push_str(",\n      \"dependencies\": ");
+            json::str_array(&mut out, node.dependencies.iter().map(String::as_str));
+            out.push_str(",\n      \"reads\": ");
+            json_accesses(&mut out, node.reads());
+            out.push_str(",\n      \"writes\": ");
+            json_accesses(&mut out, node.writes());
+            out.push_str("\n    }");
+        }
+        out.push_str(if self.nodes.is_empty() { "],\n" } else { "\n  ],\n" });
+
+        out.push_str("  \"components\": ");
+        json::str_array(&mut out, self.components());
+        out.push_str(",\n  \"resources\": ");
+        json::str_array(&mut out, self.resources());
+
+        out.push_str(",\n  \"dependencies\": [");
+        for (idx, (dependency, dependent)) in self.dependencies().into_iter().enumerate() {
+            out.push_str(if idx == 0 {
+                "\n    {\"from\": "
+            } else {
+                ",\n    {\"from\": "
+            });
+            json::write_str(&mut out, dependency);
+            out.push_str(", \"to\": ");
+            json::write_str(&mut out, dependent);
+            out.push('}');
+        }
+        out.push_str(if self.dependencies().is_empty() {
+            "],\n"
+        } else {
+            "\n  ],\n"
+        });
+
+        let conflicts = self.conflicts();
+        out.push_str("  \"conflicts\": [");
+        for (idx, conflict) in conflicts.iter().enumerate() {
+            out.push_str(if idx == 0 {
+                "\n    {\"systems\": "
+            } else {
+                ",\n    {\"systems\": "
+            });
+            json::str_array(&mut out, vec![conflict.first, conflict.second]);
+            out.push_str(", \"on\": ");
+            json::str_array(&mut out, conflict.targets.iter().cloned());
+            out.push('}');
+        }
+        out.push_str(if conflicts.is_empty() { "]\n" } else { "\n  ]\n" });
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn targets(&self, filter: impl Fn(FieldKind) -> bool) -> BTreeSet<&'static str> {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.accesses.iter())
+            .filter(|access| filter(access.kind))
+            .map(|access| access.type_name)
+            .collect()
+    }
+}
+
+fn kind_name(kind: FieldKind) -> &'static str {
+    match kind {
+        FieldKind::Entity => "entities",
+        FieldKind::Component | FieldKind::MutComponent => "component",
+        FieldKind::Resource | FieldKind::MutResource => "resource",
+    }
+}
+
+fn json_accesses<'a>(out: &mut String, accesses: impl Iterator<Item = &'a Access>) {
+    out.push('[');
+    for (idx, access) in accesses.enumerate() {
+        if idx > 0 {
+            out.push_str(", ");
+        }
+        out.push_str("{\"kind\": ");
+        json::write_str(out, kind_name(access.kind));
+        out.push_str(", \"type\": ");
+        json::write_str(out, access.type_name);
+        out.push('}');
+    }
+    out.push(']');
+}
+
+fn dot_str(value: &str) -> String {
+    format!(
+        "\"{}\"",
+        value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+    )
+}
+
+fn system_id(name: &str) -> String {
+    dot_str(&format!("system:{}", name))
+}
+
+fn target_id(kind: FieldKind, type_name: &str) -> String {
+    let prefix = if kind.is_component() { "component" } else { "resource" };
+    dot_str(&format!("{}:{}", prefix, type_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Component, Entities, Read, ReadStorage, VecStorage, WriteStorage};
+
+    struct Pos;
+    struct Vel;
+    struct DeltaTime;
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    impl Component for Vel {
+        type Storage = VecStorage<Self>;
+    }
+
+    struct Physics;
+    struct Render;
+
+    impl DslSystem for Physics {
+        const NAME: &'static str = "Physics";
+
+        fn accesses() -> Vec<Access> {
+            vec![
+                Access::new::<Pos>(FieldKind::MutComponent),
+                Access::new::<Vel>(FieldKind::Component),
+            ]
+        }
+    }
+
+    impl DslSystem for Render {
+        const NAME: &'static str = "Render";
+
+        fn accesses() -> Vec<Access> {
+            vec![
+                Access::new::<specs::world::EntitiesRes>(FieldKind::Entity),
+                Access::new::<Pos>(FieldKind::Component),
+                Access::new::<DeltaTime>(FieldKind::Resource),
+            ]
+        }
+    }
+
+    #[test]
+    fn test_conflicts_and_dependencies() {
+        let graph = SystemGraph::new()
+            .with::<Physics>("physics", &[])
+            .with::<Render>("render", &["physics"]);
+
+        assert_eq!(graph.dependencies(), vec![("physics", "render")]);
+        assert!(graph.conflicts().is_empty());
+        assert_eq!(graph.components().len(), 2);
+        assert_eq!(graph.resources().len(), 2);
+        assert!(graph
+            .to_dot()
+            .contains("\"system:physics\" -> \"system:render\" [style=bold"));
+        assert!(graph
+            .to_json()
+            .contains("\"dependencies\": [\n    {\"from\": \"physics\", \"to\": \"render\"}\n  ]"));
+    }
+
+    #[test]
+    fn test_dependency_ordered_conflicts() {
+        let graph = SystemGraph::new()
+            .with::<Physics>("physics", &[])
+            .with::<Render>("render", &["physics"])
+            .with::<Render>("overlay", &["render"])
+            .with::<Physics>("collisions", &[]);
+
+        assert!(graph.is_ordered("overlay", "physics"));
+        assert!(!graph.is_ordered("render", "collisions"));
+        let pairs: Vec<_> = graph
+            .conflicts()
+            .into_iter()
+            .map(|conflict| (conflict.first, conflict.second))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("physics", "collisions"),
+                ("render", "collisions"),
+                ("overlay", "collisions")
+            ]
+        );
+        assert_eq!(graph.conflicts()[0].targets, vec![type_name::<Pos>()]);
+    }
+
+    #[test]
+    fn test_conflict_targets_are_unique() {
+        struct Mixed;
+        struct Writer;
+
+        impl DslSystem for Mixed {
+            const NAME: &'static str = "Mixed";
+
+            fn accesses() -> Vec<Access> {
+                vec![
+                    Access::new::<Pos>(FieldKind::Component),
+                    Access::new::<Vel>(FieldKind::Component),
+                    Access::new::<Pos>(FieldKind::MutComponent),
+                ]
+            }
+        }
+
+        impl DslSystem for Writer {
+            const NAME: &'static str = "Writer";
+
+            fn accesses() -> Vec<Access> {
+                vec![
+                    Access::new::<Pos>(FieldKind::MutComponent),
+                    Access::new::<Vel>(FieldKind::MutComponent),
+                ]
+            }
+        }
+
+        let graph = SystemGraph::new()
+            .with::<Mixed>("mixed", &[])
+            .with::<Writer>("writer", &[]);
+
+        let mut expected = vec![type_name::<Pos>(), type_name::<Vel>()];
+        expected.sort();
+        assert_eq!(graph.conflicts()[0].targets, expected);
+    }
+
+    #[test]
+    fn test_thread_local_conflicts() {
+        let graph = SystemGraph::new()
+            .with::<Physics>("physics", &[])
+            .with_thread_local::<Render>("render");
+
+        assert!(graph.conflicts().is_empty());
+        assert!(graph.to_json().contains("\"thread_local\": true"));
+    }
+
+    #[test]
+    fn test_system_data_access() {
+        type Data<'a> = (Entities<'a>, WriteStorage<'a, Pos>, ReadStorage<'a, Vel>, Read<'a, u32>);
+
+        assert_eq!(
+            <Data as crate::SystemAccess>::accesses(),
+            vec![
+                Access::new::<specs::world::EntitiesRes>(FieldKind::Entity),
+                Access::new::<Pos>(FieldKind::MutComponent),
+                Access::new::<Vel>(FieldKind::Component),
+                Access::new::<u32>(FieldKind::Resource),
+            ]
+        );
+        assert_eq!(
+            short_type_name("shrev::EventChannel<a::b::Explosion>"),
+            "EventChannel<Explosion>"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::any::type_name;
+
+use specs::{join::Join, shred::Resource, Builder, Entity, RunNow, SystemData, World, WorldExt};
+
+use crate::{ComponentBundle, ItemStorages, ItemView};
+
+/// A harness for unit tests of a single system:
+///
+/// ```ignore
+/// SystemTest::new(PhysicsSystem)
+///     .spawn((Pos(0., 0.), Vel(1., 2.)))
+///     .run(3)
+///     .assert_item::<PosChange, _>(0, |item| assert_eq!(item.position.1, 6.));
+/// ```
+///
+/// The storages of the system data are registered by the system setup and the storages of spawned bundles
+/// are registered on spawn, so the test needs neither a dispatcher nor manual registration.
+pub struct SystemTest<S> {
+    world: World,
+    system: S,
+    spawned: Vec<Entity>,
+}
+
+impl<S> SystemTest<S>
+where
+    S: for<'a> RunNow<'a>,
+{
+    pub fn new(mut system: S) -> Self {
+        let mut world = World::new();
+        system.setup(&mut world);
+
+        Self {
+            world,
+            system,
+            spawned: vec![],
+        }
+    }
+
+    pub fn with_resource<R: Resource>(mut self, resource: R) -> Self {
+        self.world.insert(resource);
+        self
+    }
+
+    /// Creates an entity with the bundle components. Spawned entities are referred to by their spawn order.
+    pub fn spawn<B: ComponentBundle>(mut self, bundle: B) -> Self {
+        B::register(&mut self.world);
+        let entity = self.world.create_entity().build();
+        bundle.insert_into(&self.world, entity);
+        self.spawned.push(entity);
+        self
+    }
+
+    /// Runs the system the given number of times, maintaining the world after each run.
+    pub fn run(mut self, times: usize) -> Self {
+        for _ in 0..times {
+            self.system.run_now(&self.world);
+            self.world.maintain();
+        }
+        self
+    }
+
+    /// Returns the entity spawned with the given spawn order index.
+    pub fn entity(&self, index: usize) -> Entity {
+        *self.spawned.get(index).unwrap_or_else(|| {
+            panic!(
+                "Only {} entities are spawned, {} is out of range",
+                self.spawned.len(),
+                index
+            )
+        })
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn system(&self) -> &S {
+        &self.system
+    }
+
+    pub fn system_mut(&mut self) -> &mut S {
+        &mut self.system
+    }
+
+    /// Checks the data item `I` of the spawned entity, failing if the entity doesn't match the item.
+    /// Items with change filters cannot be checked, see `WorldQuery`.
+    pub fn assert_item<I, F>(self, index: usize, check: F) -> Self
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+        F: for<'a, 'b> FnOnce(<I as ItemView<'a, 'b>>::Item),
+    {
+        crate::query::assert_untracked::<I>();
+        let entity = self.entity(index);
+        {
+            let mut storages = <I as ItemStorages>::Storages::fetch(&self.world);
+            let entities = self.world.entities();
+            let item = I::view(&mut storages).join().get(entity, &entities).unwrap_or_else(|| {
+                panic!(
+                    "The entity {:?} does not match the data item `{}`",
+                    entity,
+                    type_name::<I>()
+                )
+            });
+            check(item.into());
+        }
+        self
+    }
+
+    /// Fails if the spawned entity matches the data item `I`.
+    pub fn assert_no_item<I>(self, index: usize) -> Self
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+    {
+        crate::query::assert_untracked::<I>();
+        let entity = self.entity(index);
+        {
+            let mut storages = <I as ItemStorages>::Storages::fetch(&self.world);
+            let entities = self.world.entities();
+            if I::view(&mut storages).join().get(entity, &entities).is_some() {
+                panic!(
+                    "The entity {:?} unexpectedly matches the data item `{}`",
+                    entity,
+                    type_name::<I>()
+                );
+            }
+        }
+        self
+    }
+
+    pub fn assert_resource<R: Resource, F: FnOnce(&R)>(self, check: F) -> Self {
+        check(&self.world.fetch::<R>());
+        self
+    }
+}
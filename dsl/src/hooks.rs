@@ -0,0 +1,149 @@
+use std::marker::PhantomData;
+
+use specs::{
+    hibitset::{BitSet, BitSetLike},
+    shred::ResourceId,
+    storage::{ComponentEvent, MaskedStorage},
+    world::EntitiesRes,
+    Component, Entities, Entity, ReadStorage, SystemData, Tracked, World,
+};
+
+use crate::{
+    changes::{read_changes, ChangeReader, SharedReader},
+    Access, ChangeFilter, ChangeReaders, ChangeTracker, FieldKind, Inserted, Removal, SystemAccess, TrackedData,
+};
+
+/// Entities whose component `T` was inserted or removed since the previous run,
+/// which drives the `#[on_insert(T)]` and `#[on_remove(T)]` methods of a system.
+///
+/// The hook reads the storage of `T`, so the system data must not write it.
+pub struct Hook<'a, T: Component, F> {
+    entities: Entities<'a>,
+    storage: ReadStorage<'a, T>,
+    affected: Vec<Entity>,
+    filter: PhantomData<F>,
+}
+
+impl<'a, T: Component, F> Hook<'a, T, F> {
+    /// Affected entities. Entities which had the component removed are reported with the generation they had it,
+    /// so deleted entities are not alive, see `Entities::is_alive`, even if their index is reused since.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.affected.iter().copied()
+    }
+
+    /// Panics if the system data `D` writes the storage of `T`, which would conflict with the hook at runtime.
+    pub fn check_access<D: SystemData<'a>>(system: &str) {
+        if D::writes().contains(&ResourceId::new::<MaskedStorage<T>>()) {
+            panic!(
+                "The system data of `{}` cannot write `{}` which is hooked by the system",
+                system,
+                std::any::type_name::<T>()
+            );
+        }
+    }
+}
+
+impl<'a, T: Component, F> SystemData<'a> for Hook<'a, T, F> {
+    fn setup(world: &mut World) {
+        <(Entities<'a>, ReadStorage<'a, T>) as SystemData<'a>>::setup(world);
+    }
+
+    fn fetch(world: &'a World) -> Self {
+        Self {
+            entities: SystemData::fetch(world),
+            storage: SystemData::fetch(world),
+            affected: vec![],
+            filter: PhantomData,
+        }
+    }
+
+    fn reads() -> Vec<ResourceId> {
+        <(Entities<'a>, ReadStorage<'a, T>) as SystemData<'a>>::reads()
+    }
+
+    fn writes() -> Vec<ResourceId> {
+        vec![]
+    }
+}
+
+impl<'a, T: Component, F> SystemAccess for Hook<'a, T, F> {
+    fn collect_accesses(accesses: &mut Vec<Access>) {
+        accesses.push(Access::new::<EntitiesRes>(FieldKind::Entity));
+        accesses.push(Access::new::<T>(FieldKind::Component));
+    }
+
+    fn collect_missing(world: &World, missing: &mut Vec<Access>) {
+        <ReadStorage<T> as SystemAccess>::collect_missing(world, missing);
+    }
+}
+
+/// The change filter of a component hook.
+pub trait HookFilter: ChangeFilter {
+    /// Whether the hook keeps the entities having the component, see `ChangeTracker::track_owners`.
+    const TRACKS_OWNERS: bool;
+}
+
+impl HookFilter for Inserted {
+    const TRACKS_OWNERS: bool = false;
+}
+
+impl HookFilter for Removal {
+    const TRACKS_OWNERS: bool = true;
+}
+
+impl<'a, T: Component, F: HookFilter> Hook<'a, T, F>
+where
+    T::Storage: Tracked,
+{
+    fn collect_insertions(&mut self, reader: &SharedReader) {
+        let mut mask = BitSet::new();
+        read_changes::<T, _, F>(&self.storage, reader, &mut mask);
+        // An index reused since the insertion is skipped unless the new entity has the component.
+        let (entities, storage) = (&self.entities, &self.storage);
+        self.affected = (&mask)
+            .iter()
+            .map(|idx| entities.entity(idx))
+            .filter(|entity| storage.contains(*entity))
+            .collect();
+    }
+
+    fn collect_removals(&mut self, reader: &SharedReader) {
+        self.affected.clear();
+        let mut reader = reader.lock().expect("The change reader is poisoned");
+        let ChangeReader { events, owners } = &mut *reader;
+        for event in self.storage.channel().read(events) {
+            match *event {
+                ComponentEvent::Inserted(idx) => {
+                    owners.insert(idx, self.entities.entity(idx));
+                }
+                ComponentEvent::Removed(idx) => self.affected.extend(owners.remove(&idx)),
+                ComponentEvent::Modified(_) => {}
+            }
+        }
+    }
+}
+
+impl<'a, T, F> TrackedData for Hook<'a, T, F>
+where
+    T: Component,
+    T::Storage: Tracked,
+    F: HookFilter,
+{
+    const READERS: usize = 1;
+
+    fn register_readers(world: &mut World, tracker: &mut ChangeTracker) {
+        tracker.register::<T>(world);
+        if F::TRACKS_OWNERS {
+            tracker.track_owners::<T>(world);
+        }
+    }
+
+    fn collect_changes(&mut self, readers: &mut ChangeReaders<'_>) {
+        let reader = readers.next::<T>();
+        if F::TRACKS_OWNERS {
+            self.collect_removals(&reader);
+        } else {
+            self.collect_insertions(&reader);
+        }
+    }
+}
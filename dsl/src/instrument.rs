@@ -0,0 +1,126 @@
+//! The views of data items with the `instrument` feature, which is enabled by the `tracing` feature.
+
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use specs::{join::Join, world::Index, ParJoin};
+
+use crate::panics;
+
+/// The view of generated system data, which instruments each joined item.
+///
+/// The items are counted into the run of a metered system the view was created in. With the `tracing` feature
+/// each item emits a debug event, a child of the span the view was created in, i.e. the system span,
+/// also on other threads of a `par_join`. The view dereferences to the joined storages,
+/// e.g. for looking up single entities. Without the feature the views are the plain joins.
+///
+/// A panic caught by a `catch_panics` system while joining a view is reported with the entity of the item.
+/// The items of a `par_join` iterated on other threads are only reported inside `with_entity_context`.
+pub struct Instrumented<J> {
+    join: J,
+    context: ItemContext,
+}
+
+/// The run of the system the view was created in.
+struct ItemContext {
+    items: Option<Arc<AtomicUsize>>,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+}
+
+impl<J> Instrumented<J> {
+    #[doc(hidden)]
+    pub fn new(join: J) -> Self {
+        let context = ItemContext {
+            items: crate::metrics::run_items(),
+            #[cfg(feature = "tracing")]
+            span: tracing::Span::current(),
+        };
+        Self { join, context }
+    }
+
+    pub fn into_inner(self) -> J {
+        self.join
+    }
+}
+
+impl<J> Deref for Instrumented<J> {
+    type Target = J;
+
+    fn deref(&self) -> &J {
+        &self.join
+    }
+}
+
+impl<J> DerefMut for Instrumented<J> {
+    fn deref_mut(&mut self) -> &mut J {
+        &mut self.join
+    }
+}
+
+impl<J: Join> Join for Instrumented<J> {
+    type Mask = J::Mask;
+    type Type = J::Type;
+    type Value = (J::Value, JoinedItems);
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        let (mask, value) = self.join.open();
+        let joined = JoinedItems {
+            context: self.context,
+            slot: panics::joined_item_slot(),
+            outer: panics::joined_item(),
+            parallel: AtomicBool::new(false),
+        };
+        (mask, (value, joined))
+    }
+
+    unsafe fn get((value, joined): &mut Self::Value, idx: Index) -> Self::Type {
+        if !panics::record_joined_item(joined.slot, idx) {
+            joined.parallel.store(true, Ordering::Relaxed);
+        }
+        let context = &joined.context;
+        if let Some(items) = &context.items {
+            items.fetch_add(1, Ordering::Relaxed);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &context.span,
+            system = context.span.metadata().map_or("", |metadata| metadata.name()),
+            index = idx,
+            "processing item"
+        );
+        J::get(value, idx)
+    }
+
+    fn is_unconstrained() -> bool {
+        J::is_unconstrained()
+    }
+}
+
+/// The items of an opened view join, which records the item being processed on the thread it was opened on.
+#[doc(hidden)]
+pub struct JoinedItems {
+    context: ItemContext,
+    slot: usize,
+    outer: Option<Index>,
+    parallel: AtomicBool,
+}
+
+impl Drop for JoinedItems {
+    fn drop(&mut self) {
+        // The item stays recorded while a panic unwinds to the system catching it, unless the panic may have
+        // happened on another thread of a `par_join`.
+        if !thread::panicking() || self.parallel.load(Ordering::Relaxed) {
+            panics::set_joined_item(self.outer);
+        }
+    }
+}
+
+// SAFETY: The instrumentation only adds atomic and thread-local writes and an event to the inner join, which is `ParJoin` itself.
+unsafe impl<J: ParJoin> ParJoin for Instrumented<J> {}
@@ -0,0 +1,69 @@
+//! A minimal JSON writer for the graph and metrics exports.
+//!
+//! The exports are available without the optional `serialize` feature, so they don't depend on `serde_json`.
+//! The tests check the escaping against `serde_json` when the feature is enabled.
+
+use std::fmt::Write;
+
+/// Appends `value` to `out` as a quoted and escaped JSON string.
+pub(crate) fn write_str(out: &mut String, value: &str) {
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", ch as u32);
+            }
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+}
+
+pub(crate) fn str_array<'a>(out: &mut String, values: impl IntoIterator<Item = &'a str>) {
+    out.push('[');
+    for (i, value) in values.into_iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write_str(out, value);
+    }
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: [&str; 4] = [
+        "plain::Name<'a>",
+        "quote \" and backslash \\",
+        "line\nfeed\r\ttab",
+        "bell \u{7} escape \u{1b} nul \u{0} unicode é ✓",
+    ];
+
+    #[test]
+    fn test_write_str_escapes_control_characters() {
+        let mut out = String::new();
+        str_array(&mut out, NAMES.iter().cloned());
+        assert_eq!(
+            out,
+            r#"["plain::Name<'a>", "quote \" and backslash \\", "line\nfeed\r\ttab", "bell \u0007 escape \u001b nul \u0000 unicode é ✓"]"#
+        );
+        assert!(!out.chars().any(char::is_control));
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn test_write_str_matches_serde_json() {
+        for name in NAMES.iter() {
+            let mut out = String::new();
+            write_str(&mut out, name);
+            assert_eq!(serde_json::from_str::<String>(&out).unwrap(), *name);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Recording of `LazyUpdate` operations for replays, enabled by the `serialize` feature.
+
+use std::{any::type_name, fmt, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use specs::{world::LazyBuilder, Builder, Component, Entity, LazyUpdate, World};
+
+use crate::short_type_name;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum LazyOpKind {
+    Create,
+    Insert,
+    Remove,
+    Exec,
+}
+
+/// A recorded `LazyUpdate` operation. The entity is its index and generation, the component is its type name.
+///
+/// Type names are not stable across compiler versions, so replays compare operations with `LazyOp::matches`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LazyOp {
+    pub kind: LazyOpKind,
+    pub entity: Option<(u32, i32)>,
+    pub component: Option<String>,
+}
+
+impl LazyOp {
+    fn new(kind: LazyOpKind, entity: Option<Entity>, component: Option<&str>) -> Self {
+        Self {
+            kind,
+            entity: entity.map(|entity| (entity.id(), entity.gen().id())),
+            component: component.map(str::to_string),
+        }
+    }
+
+    /// Whether the operations have the same kind, entity and component.
+    /// The components are compared without their module paths.
+    pub fn matches(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.entity == other.entity && self.short_component() == other.short_component()
+    }
+
+    fn short_component(&self) -> Option<String> {
+        self.component.as_deref().map(short_type_name)
+    }
+}
+
+impl fmt::Display for LazyOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.kind)?;
+        if let Some(component) = &self.component {
+            write!(f, " `{}`", short_type_name(component))?;
+        }
+        if let Some((id, gen)) = self.entity {
+            write!(f, " of entity {}:{}", id, gen)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `LazyUpdate` operations of the current tick which systems opt in to record, through a shared reference:
+///
+/// ```ignore
+/// recorder.record(&updater).insert(shrapnel, Vel(angle.sin(), angle.cos()));
+/// ```
+///
+/// Only the operations issued through `record` are recorded. Operations issued on the `LazyUpdate` directly
+/// are not, so a replay does not check them.
+#[derive(Debug, Default)]
+pub struct LazyRecorder {
+    ops: Mutex<Vec<LazyOp>>,
+}
+
+impl LazyRecorder {
+    pub fn record<'a>(&'a self, lazy: &'a LazyUpdate) -> RecordedLazy<'a> {
+        RecordedLazy { lazy, recorder: self }
+    }
+
+    fn push(&self, op: LazyOp) {
+        self.ops.lock().expect("The lazy recorder lock is poisoned").push(op);
+    }
+
+    /// Takes the operations sorted, since parallel systems record them in any order.
+    /// The components are sorted without their module paths, like `LazyOp::matches` compares them.
+    pub fn take(&mut self) -> Vec<LazyOp> {
+        let ops = self.ops.get_mut().expect("The lazy recorder lock is poisoned");
+        let mut ops = std::mem::take(ops);
+        ops.sort_by_cached_key(|op| (op.kind, op.entity, op.short_component()));
+        ops
+    }
+}
+
+/// Recording `LazyUpdate` operations, see `LazyRecorder::record`.
+pub struct RecordedLazy<'a> {
+    lazy: &'a LazyUpdate,
+    recorder: &'a LazyRecorder,
+}
+
+impl<'a> RecordedLazy<'a> {
+    pub fn create_entity(&self, entities: &specs::world::EntitiesRes) -> RecordedBuilder<'a> {
+        let builder = self.lazy.create_entity(entities);
+        self.recorder
+            .push(LazyOp::new(LazyOpKind::Create, Some(builder.entity), None));
+        RecordedBuilder {
+            builder,
+            recorder: self.recorder,
+        }
+    }
+
+    pub fn insert<C: Component + Send + Sync>(&self, entity: Entity, component: C) {
+        self.recorder
+            .push(LazyOp::new(LazyOpKind::Insert, Some(entity), Some(type_name::<C>())));
+        self.lazy.insert(entity, component);
+    }
+
+    pub fn remove<C: Component + Send + Sync>(&self, entity: Entity) {
+        self.recorder
+            .push(LazyOp::new(LazyOpKind::Remove, Some(entity), Some(type_name::<C>())));
+        self.lazy.remove::<C>(entity);
+    }
+
+    /// Executes the closure on `maintain`. Only the call is recorded, so keep its effects deterministic.
+    pub fn exec<F: FnOnce(&mut World) + Send + Sync + 'static>(&self, f: F) {
+        self.recorder.push(LazyOp::new(LazyOpKind::Exec, None, None));
+        self.lazy.exec_mut(f);
+    }
+}
+
+/// A `LazyBuilder` recording its components, see `RecordedLazy::create_entity`.
+pub struct RecordedBuilder<'a> {
+    builder: LazyBuilder<'a>,
+    recorder: &'a LazyRecorder,
+}
+
+impl<'a> Builder for RecordedBuilder<'a> {
+    fn with<C: Component + Send + Sync>(self, component: C) -> Self {
+        self.recorder.push(LazyOp::new(
+            LazyOpKind::Insert,
+            Some(self.builder.entity),
+            Some(type_name::<C>()),
+        ));
+        Self {
+            builder: self.builder.with(component),
+            recorder: self.recorder,
+        }
+    }
+
+    fn build(self) -> Entity {
+        self.builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_op_matches() {
+        let op = |component: &str| LazyOp {
+            kind: LazyOpKind::Insert,
+            entity: Some((1, 1)),
+            component: Some(component.to_string()),
+        };
+
+        assert!(op("game::Pos").matches(&op("game::physics::Pos")));
+        assert!(!op("game::Pos").matches(&op("game::Vel")));
+        assert!(!op("game::Pos").matches(&LazyOp {
+            kind: LazyOpKind::Remove,
+            ..op("game::Pos")
+        }));
+    }
+}
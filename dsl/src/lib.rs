@@ -1,7 +1,47 @@
+pub extern crate log;
+#[cfg(feature = "serialize")]
+pub extern crate serde;
 pub extern crate specs;
+#[cfg(feature = "tracing")]
+pub extern crate tracing;
 
-pub use specs_dsl_attributes::{data_item, system};
+pub use specs_dsl_attributes::{data_item, resource_item, system};
 
-pub use self::system_data::*;
+#[cfg(feature = "instrument")]
+pub use self::instrument::*;
+#[cfg(feature = "serialize")]
+pub use self::{lazy::*, replay::*, saveload::*};
 
+pub use self::{
+    access::*, batch::*, bundle::*, capture::*, changes::*, errors::*, events::*, fetch::*, graph::*, harness::*,
+    hooks::*, metrics::*, panics::*, query::*, report::*, selection::*, system::*, system_data::*, trace::*,
+};
+
+mod access;
+mod batch;
+mod bundle;
+mod capture;
+mod changes;
+mod errors;
+mod events;
+mod fetch;
+mod graph;
+mod harness;
+mod hooks;
+#[cfg(feature = "instrument")]
+mod instrument;
+mod json;
+#[cfg(feature = "serialize")]
+mod lazy;
+mod metrics;
+mod panics;
+mod query;
+#[cfg(feature = "serialize")]
+mod replay;
+mod report;
+#[cfg(feature = "serialize")]
+mod saveload;
+mod selection;
+mod system;
 mod system_data;
+mod trace;
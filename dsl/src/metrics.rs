@@ -0,0 +1,325 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    fmt::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use specs::{join::Join, world::Index, ParJoin};
+
+use crate::json;
+
+thread_local! {
+    static RUN_ITEMS: RefCell<Option<Arc<AtomicUsize>>> = const { RefCell::new(None) };
+}
+
+/// The item count of a run of a system declared with `#[system(Data, metrics)]`.
+///
+/// The joins created on the thread of the run count into it, also when they are iterated on other threads.
+/// Each run has its own count, so systems of different worlds or dispatchers are counted independently.
+#[doc(hidden)]
+pub struct RunItems {
+    items: Arc<AtomicUsize>,
+    outer: Option<Arc<AtomicUsize>>,
+}
+
+impl RunItems {
+    pub fn start() -> Self {
+        let items = Arc::new(AtomicUsize::new(0));
+        let outer = RUN_ITEMS.with(|current| current.replace(Some(items.clone())));
+        Self { items, outer }
+    }
+
+    pub fn add(&self, count: usize) {
+        self.items.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> usize {
+        self.items.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for RunItems {
+    fn drop(&mut self) {
+        RUN_ITEMS.with(|current| *current.borrow_mut() = self.outer.take());
+    }
+}
+
+/// The item count of the metered system running on this thread.
+pub(crate) fn run_items() -> Option<Arc<AtomicUsize>> {
+    RUN_ITEMS.with(|current| current.borrow().clone())
+}
+
+/// Counts the items of hand-written joins in metered systems, e.g. `(&positions, &velocities).counted().par_join()`.
+///
+/// The views of data items are counted with the `instrument` feature.
+pub trait JoinCount: Join + Sized {
+    fn counted(self) -> Counted<Self> {
+        Counted {
+            join: self,
+            items: run_items(),
+        }
+    }
+}
+
+impl<J: Join> JoinCount for J {}
+
+/// A join counting its items into the run of a metered system, see `JoinCount`.
+pub struct Counted<J> {
+    join: J,
+    items: Option<Arc<AtomicUsize>>,
+}
+
+impl<J: Join> Join for Counted<J> {
+    type Mask = J::Mask;
+    type Type = J::Type;
+    type Value = (J::Value, Option<Arc<AtomicUsize>>);
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        let (mask, value) = self.join.open();
+        (mask, (value, self.items))
+    }
+
+    unsafe fn get((value, items): &mut Self::Value, idx: Index) -> Self::Type {
+        if let Some(items) = items {
+            items.fetch_add(1, Ordering::Relaxed);
+        }
+        J::get(value, idx)
+    }
+}
+
+// SAFETY: Counting only adds an atomic increment to the inner join, which is `ParJoin` itself.
+unsafe impl<J: ParJoin> ParJoin for Counted<J> {}
+
+/// A single run of a metered system.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sample {
+    pub duration: Duration,
+    pub items: usize,
+}
+
+/// Run statistics of a metered system. The samples are the last runs, oldest first.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemStats {
+    pub runs: u64,
+    pub min: Duration,
+    pub max: Duration,
+    pub total: Duration,
+    pub items: u64,
+    pub samples: VecDeque<Sample>,
+}
+
+impl SystemStats {
+    fn new() -> Self {
+        Self {
+            runs: 0,
+            min: Duration::from_secs(u64::MAX),
+            max: Duration::default(),
+            total: Duration::default(),
+            items: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    pub fn avg(&self) -> Duration {
+        Duration::from_nanos((self.total.as_nanos() / u128::from(self.runs.max(1))) as u64)
+    }
+
+    pub fn avg_items(&self) -> f64 {
+        self.items as f64 / self.runs.max(1) as f64
+    }
+}
+
+/// Timings and item counts of systems declared with `#[system(Data, metrics)]`, by system name.
+///
+/// Systems record runs through a shared reference, so metered systems still run in parallel.
+/// Runs skipped by run conditions are not recorded.
+#[derive(Debug)]
+pub struct SystemMetrics {
+    sample_count: usize,
+    systems: Mutex<BTreeMap<&'static str, SystemStats>>,
+}
+
+impl Default for SystemMetrics {
+    fn default() -> Self {
+        Self::new(120)
+    }
+}
+
+impl SystemMetrics {
+    /// Keeps the given number of the last samples per system.
+    /// Insert it before the dispatcher setup to override the default of 120 samples.
+    pub fn new(sample_count: usize) -> Self {
+        Self {
+            sample_count,
+            systems: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn record(&self, system: &'static str, duration: Duration, items: usize) {
+        let mut systems = self.systems.lock().expect("The system metrics lock is poisoned");
+        let stats = systems.entry(system).or_insert_with(SystemStats::new);
+        stats.runs += 1;
+        stats.min = stats.min.min(duration);
+        stats.max = stats.max.max(duration);
+        stats.total += duration;
+        stats.items += items as u64;
+        if stats.samples.len() == self.sample_count {
+            stats.samples.pop_front();
+        }
+        if self.sample_count > 0 {
+            stats.samples.push_back(Sample { duration, items });
+        }
+    }
+
+    pub fn stats(&self, system: &str) -> Option<SystemStats> {
+        let systems = self.systems.lock().expect("The system metrics lock is poisoned");
+        systems.get(system).cloned()
+    }
+
+    pub fn clear(&mut self) {
+        self.systems
+            .get_mut()
+            .expect("The system metrics lock is poisoned")
+            .clear();
+    }
+
+    /// The statistics of all systems, one row per system with durations in microseconds.
+    pub fn to_csv(&self) -> String {
+        let systems = self.systems.lock().expect("The system metrics lock is poisoned");
+        let mut out = String::from("system,runs,min_us,avg_us,max_us,avg_items\n");
+        for (system, stats) in systems.iter() {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{},{:.2}",
+                csv_str(system),
+                stats.runs,
+                micros(stats.min),
+                micros(stats.avg()),
+                micros(stats.max),
+                stats.avg_items()
+            );
+        }
+        out
+    }
+
+    /// The samples of all systems, one row per run with durations in microseconds.
+    pub fn samples_to_csv(&self) -> String {
+        let systems = self.systems.lock().expect("The system metrics lock is poisoned");
+        let mut out = String::from("system,duration_us,items\n");
+        for (system, stats) in systems.iter() {
+            for sample in &stats.samples {
+                let _ = writeln!(out, "{},{},{}", csv_str(system), micros(sample.duration), sample.items);
+            }
+        }
+        out
+    }
+
+    /// The statistics and samples of all systems, with durations in microseconds.
+    pub fn to_json(&self) -> String {
+        let systems = self.systems.lock().expect("The system metrics lock is poisoned");
+        let mut out = String::new();
+        out.push_str("{\n  \"systems\": [");
+        for (idx, (system, stats)) in systems.iter().enumerate() {
+            out.push_str(if idx == 0 { "\n" } else { ",\n" });
+            out.push_str("    {\n      \"name\": ");
+            json::write_str(&mut out, system);
+            let _ = write!(out, ",\n      \"runs\": {}", stats.runs);
+            let _ = write!(out, ",\n      \"min_us\": {}", micros(stats.min));
+            let _ = write!(out, ",\n      \"avg_us\": {}", micros(stats.avg()));
+            let _ = write!(out, ",\n      \"max_us\": {}", micros(stats.max));
+            let _ = write!(out, ",\n      \"avg_items\": {:.2}", stats.avg_items());
+            out.push_str(",\n      \"samples\": [");
+            for (idx, sample) in stats.samples.iter().enumerate() {
+                if idx > 0 {
+                    out.push_str(", ");
+                }
+                let _ = write!(
+                    out,
+                    "{{\"duration_us\": {}, \"items\": {}}}",
+                    micros(sample.duration),
+                    sample.items
+                );
+            }
+            out.push_str("]\n    }");
+        }
+        out.push_str(if systems.is_empty() { "]\n" } else { "\n  ]\n" });
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Formats the duration in microseconds, keeping the nanoseconds of short runs.
+fn micros(duration: Duration) -> String {
+    format!("{:.3}", duration.as_nanos() as f64 / 1000.)
+}
+
+fn csv_str(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, Component, ReadStorage, VecStorage, World, WorldExt};
+
+    struct Pos;
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    #[test]
+    fn test_system_metrics() {
+        let mut world = World::new();
+        world.register::<Pos>();
+        world.create_entity().with(Pos).build();
+        world.create_entity().with(Pos).build();
+        let positions: ReadStorage<Pos> = world.system_data();
+        assert_eq!(
+            (&positions).counted().join().count(),
+            2,
+            "joins outside of runs are not counted"
+        );
+
+        let metrics = SystemMetrics::new(2);
+        for millis in 1..=3 {
+            let items = RunItems::start();
+            if millis == 1 {
+                assert_eq!((&positions).counted().join().count(), 2);
+            }
+            metrics.record("Physics", Duration::from_millis(millis), items.count());
+        }
+        assert!(run_items().is_none());
+        let stats = metrics.stats("Physics").unwrap();
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.avg(), Duration::from_millis(2));
+        assert_eq!(stats.samples.len(), 2);
+        assert_eq!(
+            metrics.to_csv(),
+            "system,runs,min_us,avg_us,max_us,avg_items\nPhysics,3,1000.000,2000.000,3000.000,0.67\n"
+        );
+        assert_eq!(
+            metrics.samples_to_csv(),
+            "system,duration_us,items\nPhysics,2000.000,0\nPhysics,3000.000,0\n"
+        );
+        assert!(metrics.to_json().contains(
+            "\"samples\": [{\"duration_us\": 2000.000, \"items\": 0}, {\"duration_us\": 3000.000, \"items\": 0}]"
+        ));
+    }
+
+    #[test]
+    fn test_csv_str() {
+        assert_eq!(csv_str("Physics"), "Physics");
+        assert_eq!(csv_str("Batch<A, B>"), "\"Batch<A, B>\"");
+        assert_eq!(csv_str("say \"hi\"\r\n"), "\"say \"\"hi\"\"\r\n\"");
+    }
+}
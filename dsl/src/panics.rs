@@ -0,0 +1,197 @@
+use std::{
+    any::Any,
+    cell::Cell,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::Mutex,
+};
+
+use specs::{world::EntitiesRes, world::Index, Entity};
+
+/// A panic caught in a system declared with `#[system(Data, catch_panics)]`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SystemPanic {
+    pub system: &'static str,
+    /// The entity being processed, if the panic happened inside `with_entity_context`,
+    /// or in a sequential join of a data item view with the `instrument` feature.
+    pub entity: Option<Entity>,
+    pub message: String,
+}
+
+impl SystemPanic {
+    pub fn new(system: &'static str, payload: Box<dyn Any + Send>) -> Self {
+        let (entity, payload) = match payload.downcast::<EntityPanic>() {
+            Ok(entity_panic) => (Some(entity_panic.entity), entity_panic.payload),
+            Err(payload) => (None, payload),
+        };
+        let message = if let Some(message) = payload.downcast_ref::<&str>() {
+            message.to_string()
+        } else if let Some(message) = payload.downcast_ref::<String>() {
+            message.clone()
+        } else {
+            "Box<dyn Any>".to_string()
+        };
+
+        Self {
+            system,
+            entity,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for SystemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The system `{}` panicked", self.system)?;
+        if let Some(entity) = self.entity {
+            write!(f, " processing {:?}", entity)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Panics caught in systems with the `catch_panics` option, in the order they were caught.
+///
+/// The rest of the panicked run is skipped, so the world may be left half-updated,
+/// e.g. with some of the items of a join changed.
+#[derive(Debug, Default)]
+pub struct SystemPanics {
+    panics: Mutex<Vec<SystemPanic>>,
+}
+
+impl SystemPanics {
+    pub fn push(&self, panic: SystemPanic) {
+        self.panics
+            .lock()
+            .expect("The system panics lock is poisoned")
+            .push(panic);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.panics
+            .lock()
+            .expect("The system panics lock is poisoned")
+            .is_empty()
+    }
+
+    /// Takes the caught panics, e.g. after each dispatch.
+    pub fn take(&mut self) -> Vec<SystemPanic> {
+        mem::take(self.panics.get_mut().expect("The system panics lock is poisoned"))
+    }
+}
+
+thread_local! {
+    static JOINED_ITEM: Cell<Option<Index>> = const { Cell::new(None) };
+}
+
+/// The item of the view join being iterated on this thread.
+#[cfg(feature = "instrument")]
+pub(crate) fn joined_item() -> Option<Index> {
+    JOINED_ITEM.with(Cell::get)
+}
+
+pub(crate) fn set_joined_item(item: Option<Index>) {
+    JOINED_ITEM.with(|joined| joined.set(item));
+}
+
+/// The address of the joined item of this thread, which identifies the thread.
+#[cfg(feature = "instrument")]
+pub(crate) fn joined_item_slot() -> usize {
+    JOINED_ITEM.with(|joined| joined as *const _ as usize)
+}
+
+/// Records the item if this is the thread of the slot.
+#[cfg(feature = "instrument")]
+pub(crate) fn record_joined_item(slot: usize, item: Index) -> bool {
+    JOINED_ITEM.with(|joined| {
+        let own = joined as *const _ as usize == slot;
+        if own {
+            joined.set(Some(item));
+        }
+        own
+    })
+}
+
+/// A run of a system declared with `#[system(Data, catch_panics)]`.
+#[doc(hidden)]
+pub struct PanicScope {
+    outer: Option<Index>,
+}
+
+impl PanicScope {
+    pub fn start() -> Self {
+        Self {
+            outer: JOINED_ITEM.with(|joined| joined.replace(None)),
+        }
+    }
+
+    /// The caught panic with the entity of the innermost `with_entity_context`, or else of the joined item.
+    pub fn caught(&self, system: &'static str, payload: Box<dyn Any + Send>, entities: &EntitiesRes) -> SystemPanic {
+        let mut caught = SystemPanic::new(system, payload);
+        let joined = JOINED_ITEM.with(|joined| joined.replace(None));
+        if caught.entity.is_none() {
+            caught.entity = joined.map(|idx| entities.entity(idx));
+        }
+        caught
+    }
+}
+
+impl Drop for PanicScope {
+    fn drop(&mut self) {
+        set_joined_item(self.outer);
+    }
+}
+
+/// A panic payload tagged with the entity being processed.
+struct EntityPanic {
+    entity: Entity,
+    payload: Box<dyn Any + Send>,
+}
+
+/// Processes a single entity, so a panic inside `f` is reported with the entity.
+///
+/// The entity travels with the panic payload, so it is reported from parallel joins as well:
+///
+/// ```ignore
+/// (&entities, &mut positions).par_join().for_each(|(entity, position)| {
+///     with_entity_context(entity, || position.0 += 1.);
+/// });
+/// ```
+pub fn with_entity_context<R>(entity: Entity, f: impl FnOnce() -> R) -> R {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(result) => result,
+        // The innermost entity is the one being processed.
+        Err(payload) if payload.is::<EntityPanic>() => panic::resume_unwind(payload),
+        Err(payload) => panic::resume_unwind(Box::new(EntityPanic { entity, payload })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, World, WorldExt};
+
+    #[test]
+    fn test_system_panic() {
+        let mut world = World::new();
+        let entity = world.create_entity().build();
+
+        let payload = panic::catch_unwind(|| with_entity_context(entity, || panic!("no position"))).unwrap_err();
+        let caught = SystemPanic::new("PhysicsSystem", payload);
+        assert_eq!(caught.entity, Some(entity));
+        assert_eq!(
+            caught.to_string(),
+            format!(
+                "The system `PhysicsSystem` panicked processing {:?}: no position",
+                entity
+            )
+        );
+
+        let mut panics = SystemPanics::default();
+        panics.push(caught);
+        panics.push(SystemPanic::new("RenderSystem", Box::new(String::from("no window"))));
+        let taken = panics.take();
+        assert_eq!(taken[1].to_string(), "The system `RenderSystem` panicked: no window");
+        assert!(panics.is_empty());
+    }
+}
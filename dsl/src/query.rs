@@ -0,0 +1,73 @@
+use std::any::type_name;
+
+use specs::{join::Join, SystemData, World};
+
+use crate::{ItemStorages, ItemView};
+
+/// Iterates data items outside of systems, e.g. in renderers, tools and tests:
+///
+/// ```ignore
+/// world.query::<Rendered, _>(|item| draw(item.position));
+/// world.query_mut::<PosChange, _>(|item| item.position.0 += item.velocity.0);
+/// ```
+///
+/// Items with change filters panic, since they join the changes between the runs of a system.
+pub trait WorldQuery {
+    /// Calls `f` with each data item `I`. The item must not write any storage or resource, see `query_mut`.
+    fn query<I, F>(&self, f: F)
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+        F: for<'a, 'b> FnMut(<I as ItemView<'a, 'b>>::Item);
+
+    /// Calls `f` with each data item `I`, which may write its storages and resources.
+    fn query_mut<I, F>(&mut self, f: F)
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+        F: for<'a, 'b> FnMut(<I as ItemView<'a, 'b>>::Item);
+}
+
+impl WorldQuery for World {
+    fn query<I, F>(&self, f: F)
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+        F: for<'a, 'b> FnMut(<I as ItemView<'a, 'b>>::Item),
+    {
+        if !<<I as ItemStorages>::Storages as SystemData>::writes().is_empty() {
+            panic!(
+                "The data item `{}` writes the world, it can be queried only by `query_mut`",
+                type_name::<I>()
+            );
+        }
+        for_each_item::<I, F>(self, f);
+    }
+
+    fn query_mut<I, F>(&mut self, f: F)
+    where
+        I: for<'a, 'b> ItemView<'a, 'b>,
+        F: for<'a, 'b> FnMut(<I as ItemView<'a, 'b>>::Item),
+    {
+        for_each_item::<I, F>(self, f);
+    }
+}
+
+fn for_each_item<I, F>(world: &World, mut f: F)
+where
+    I: for<'a, 'b> ItemView<'a, 'b>,
+    F: for<'a, 'b> FnMut(<I as ItemView<'a, 'b>>::Item),
+{
+    assert_untracked::<I>();
+    let mut storages: <I as ItemStorages>::Storages = world.system_data();
+    for item in I::view(&mut storages).join() {
+        f(item.into());
+    }
+}
+
+/// Change filters join the changes since the previous run of a system, so joins outside systems would match nothing.
+pub(crate) fn assert_untracked<I: for<'b> ItemStorages<'b>>() {
+    if <I as ItemStorages>::TRACKS_CHANGES {
+        panic!(
+            "The data item `{}` has change filters, it can be joined only by systems",
+            type_name::<I>()
+        );
+    }
+}
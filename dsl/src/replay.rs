@@ -0,0 +1,395 @@
+use std::{collections::BTreeMap, error::Error, fmt, hash::Hasher};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use specs::{shred::Resource, World, WorldExt};
+
+use crate::{LazyOp, LazyRecorder};
+
+/// Hashes a part of the world state, e.g. the `hash_state` of a data item declared with `#[data_item(hash)]`.
+pub type StateHook = fn(&World, &mut dyn Hasher);
+
+type ResourceLoader = fn(&mut World, serde_json::Value) -> Result<(), serde_json::Error>;
+
+/// The 64-bit FNV-1a hasher. Unlike the std hasher, its hashes are stable across builds and platforms:
+/// integers are hashed as little-endian bytes, and `usize`, e.g. the length of a slice, as 64 bits.
+pub struct StateHasher(u64);
+
+impl Default for StateHasher {
+    fn default() -> Self {
+        StateHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for StateHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    // Signed integers are written through the unsigned methods of the same width.
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+}
+
+/// Hashes the world state with the hooks in order.
+pub fn state_hash(world: &World, hooks: &[StateHook]) -> u64 {
+    let mut hasher = StateHasher::default();
+    for hook in hooks {
+        hook(world, &mut hasher);
+    }
+    hasher.finish()
+}
+
+/// A resource injected before a tick, by the key it was recorded with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedResource {
+    pub key: String,
+    pub value: serde_json::Value,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TickRecord {
+    pub resources: Vec<RecordedResource>,
+    pub updates: Vec<LazyOp>,
+    pub hash: u64,
+}
+
+/// The ticks recorded by `Recorder` and checked by `Replayer`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub ticks: Vec<TickRecord>,
+}
+
+impl Recording {
+    pub fn to_json(&self) -> Result<String, ReplayError> {
+        serde_json::to_string(self).map_err(ReplayError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ReplayError> {
+        serde_json::from_str(json).map_err(ReplayError::Json)
+    }
+}
+
+/// Records the injected resources, the `LazyRecorder` operations and the state hash of every tick:
+///
+/// ```ignore
+/// let mut recorder = Recorder::new().hash_with(PosChange::hash_state);
+/// recorder.insert(&mut world, "delta_time", DeltaTime(0.016))?;
+/// recorder.tick(&mut world, |world| dispatcher.dispatch(world));
+/// std::fs::write("replay.json", recorder.recording().to_json()?)?;
+/// ```
+#[derive(Default)]
+pub struct Recorder {
+    hooks: Vec<StateHook>,
+    resources: Vec<RecordedResource>,
+    recording: Recording,
+}
+
+impl Recorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hash_with(mut self, hook: StateHook) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Inserts the resource into the world and records it for the next tick under the key.
+    ///
+    /// The key identifies the resource type in the recording, so keep it when the type is renamed or moved.
+    pub fn insert<R: Resource + Serialize>(
+        &mut self,
+        world: &mut World,
+        key: &str,
+        resource: R,
+    ) -> Result<(), ReplayError> {
+        self.resources.push(RecordedResource {
+            key: key.to_string(),
+            value: serde_json::to_value(&resource).map_err(ReplayError::Json)?,
+        });
+        world.insert(resource);
+        Ok(())
+    }
+
+    /// Runs the tick, e.g. dispatches the systems, maintains the world and records it.
+    pub fn tick(&mut self, world: &mut World, run: impl FnOnce(&mut World)) {
+        run(world);
+        world.maintain();
+        self.recording.ticks.push(TickRecord {
+            resources: std::mem::take(&mut self.resources),
+            updates: take_updates(world),
+            hash: state_hash(world, &self.hooks),
+        });
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    pub fn into_recording(self) -> Recording {
+        self.recording
+    }
+}
+
+/// Re-runs the recorded ticks and checks the `LazyRecorder` operations and the state hash of every tick.
+///
+/// The world must be set up like the recorded one, with the same hooks and the recorded resource keys:
+///
+/// ```ignore
+/// let replayer = Replayer::new().resource::<DeltaTime>("delta_time").hash_with(PosChange::hash_state);
+/// replayer.replay(&recording, &mut world, |world| dispatcher.dispatch(world))?;
+/// ```
+#[derive(Default)]
+pub struct Replayer {
+    hooks: Vec<StateHook>,
+    loaders: BTreeMap<&'static str, ResourceLoader>,
+}
+
+impl Replayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hash_with(mut self, hook: StateHook) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Loads the resources recorded under the key.
+    pub fn resource<R: Resource + DeserializeOwned>(mut self, key: &'static str) -> Self {
+        self.loaders.insert(key, |world, value| {
+            world.insert(serde_json::from_value::<R>(value)?);
+            Ok(())
+        });
+        self
+    }
+
+    /// Stops at the first tick which diverges from the recording.
+    pub fn replay(
+        &self,
+        recording: &Recording,
+        world: &mut World,
+        mut run: impl FnMut(&mut World),
+    ) -> Result<(), ReplayError> {
+        for (tick, record) in recording.ticks.iter().enumerate() {
+            for resource in &record.resources {
+                let loader = self
+                    .loaders
+                    .get(resource.key.as_str())
+                    .ok_or_else(|| ReplayError::UnknownResource(resource.key.clone()))?;
+                loader(world, resource.value.clone()).map_err(ReplayError::Json)?;
+            }
+
+            run(world);
+            world.maintain();
+
+            let updates = take_updates(world);
+            let matches = |expected: &[LazyOp], actual: &[LazyOp]| {
+                expected.len() == actual.len()
+                    && expected
+                        .iter()
+                        .zip(actual)
+                        .all(|(expected, actual)| expected.matches(actual))
+            };
+            if !matches(&record.updates, &updates) {
+                let (expected, actual) = first_difference(&record.updates, &updates);
+                return Err(ReplayError::UpdatesDiverged { tick, expected, actual });
+            }
+            let actual = state_hash(world, &self.hooks);
+            if actual != record.hash {
+                return Err(ReplayError::HashDiverged {
+                    tick,
+                    expected: record.hash,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn take_updates(world: &mut World) -> Vec<LazyOp> {
+    world
+        .entry::<LazyRecorder>()
+        .or_insert_with(LazyRecorder::default)
+        .take()
+}
+
+fn first_difference(expected: &[LazyOp], actual: &[LazyOp]) -> (Option<LazyOp>, Option<LazyOp>) {
+    let idx = expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| !expected.matches(actual))
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    (expected.get(idx).cloned(), actual.get(idx).cloned())
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Json(serde_json::Error),
+    /// The recording has a resource key not registered with `Replayer::resource`.
+    UnknownResource(String),
+    /// The first differing operation of the tick, or `None` if the other side has extra operations.
+    UpdatesDiverged {
+        tick: usize,
+        expected: Option<LazyOp>,
+        actual: Option<LazyOp>,
+    },
+    HashDiverged {
+        tick: usize,
+        expected: u64,
+        actual: u64,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = |op: &Option<LazyOp>| op.as_ref().map_or("nothing".to_string(), LazyOp::to_string);
+        match self {
+            ReplayError::Json(error) => write!(f, "Cannot encode the recording: {}", error),
+            ReplayError::UnknownResource(name) => write!(f, "The recorded resource `{}` is not registered", name),
+            ReplayError::UpdatesDiverged { tick, expected, actual } => write!(
+                f,
+                "The lazy updates of tick {} diverged: expected {}, got {}",
+                tick,
+                op(expected),
+                op(actual)
+            ),
+            ReplayError::HashDiverged { tick, expected, actual } => write!(
+                f,
+                "The state of tick {} diverged: expected hash {:016x}, got {:016x}",
+                tick, expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, Component, Entities, Join, LazyUpdate, Read, ReadStorage, RunNow, System, VecStorage};
+    use std::hash::Hash;
+
+    #[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+    struct Step(u32);
+
+    #[derive(Hash)]
+    struct Pos(u32);
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    struct Spawner;
+
+    impl<'a> System<'a> for Spawner {
+        type SystemData = (
+            Entities<'a>,
+            Read<'a, Step>,
+            Read<'a, LazyUpdate>,
+            Read<'a, LazyRecorder>,
+        );
+
+        fn run(&mut self, (entities, step, updater, recorder): Self::SystemData) {
+            recorder
+                .record(&updater)
+                .create_entity(&entities)
+                .with(Pos(step.0))
+                .build();
+        }
+    }
+
+    fn hash_positions(world: &World, mut state: &mut dyn Hasher) {
+        let (entities, positions): (Entities, ReadStorage<Pos>) = world.system_data();
+        for (entity, position) in (&entities, &positions).join() {
+            entity.hash(&mut state);
+            position.hash(&mut state);
+        }
+    }
+
+    fn setup() -> World {
+        let mut world = World::new();
+        world.register::<Pos>();
+        System::setup(&mut Spawner, &mut world);
+        world
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut world = setup();
+        let mut recorder = Recorder::new().hash_with(hash_positions);
+        for step in 1..=3 {
+            recorder.insert(&mut world, "step", Step(step)).unwrap();
+            recorder.tick(&mut world, |world| Spawner.run_now(world));
+        }
+        let recording = Recording::from_json(&recorder.recording().to_json().unwrap()).unwrap();
+        assert_eq!(&recording, recorder.recording());
+        assert_eq!(recording.ticks[1].resources[0].value, serde_json::json!(2));
+        assert_eq!(recording.ticks[1].updates.len(), 2);
+
+        let replayer = Replayer::new().resource::<Step>("step").hash_with(hash_positions);
+        replayer
+            .replay(&recording, &mut setup(), |world| Spawner.run_now(world))
+            .unwrap();
+
+        let error = replayer
+            .replay(&recording, &mut setup(), |world| {
+                Spawner.run_now(world);
+                world.create_entity().with(Pos(0)).build();
+            })
+            .unwrap_err();
+        assert!(matches!(error, ReplayError::HashDiverged { tick: 0, .. }));
+        let error = replayer.replay(&recording, &mut setup(), |_| {}).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The lazy updates of tick 0 diverged: expected Create of entity 0:1, got nothing"
+        );
+
+        let error = Replayer::new()
+            .replay(&recording, &mut setup(), |world| Spawner.run_now(world))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "The recorded resource `step` is not registered");
+    }
+
+    #[test]
+    fn test_state_hasher_endianness() {
+        let hash = |value: &dyn Fn(&mut StateHasher)| {
+            let mut hasher = StateHasher::default();
+            value(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(
+            hash(&|hasher| 1u32.hash(hasher)),
+            hash(&|hasher| hasher.write(&[1, 0, 0, 0]))
+        );
+        assert_eq!(
+            hash(&|hasher| [7u8].hash(hasher)),
+            hash(&|hasher| hasher.write(&[1, 0, 0, 0, 0, 0, 0, 0, 7]))
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::{any::type_name, fmt};
+
+use specs::{shred::Resource, storage::MaskedStorage, Component, Entity, World, WorldExt};
+
+use crate::short_type_name;
+
+/// Explains whether an entity matches a data item, see the generated `diagnose` method of data items.
+///
+/// Change filters and removed components depend on the change readers of a system, so they are not checked.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchReport {
+    pub item: &'static str,
+    pub entity: Entity,
+    pub is_dead: bool,
+    /// Type names of the components the entity lacks and of the resources absent from the world.
+    pub missing: Vec<&'static str>,
+}
+
+impl MatchReport {
+    pub fn new<I: ?Sized>(world: &World, entity: Entity) -> Self {
+        Self {
+            item: type_name::<I>(),
+            entity,
+            is_dead: !world.entities().is_alive(entity),
+            missing: vec![],
+        }
+    }
+
+    /// Components of dead entities are not checked, since their index may be reused by another entity.
+    pub fn check_component<T: Component>(&mut self, world: &World) {
+        if self.is_dead {
+            return;
+        }
+        let has_component =
+            world.has_value::<MaskedStorage<T>>() && world.read_storage::<T>().mask().contains(self.entity.id());
+        if !has_component {
+            self.missing.push(type_name::<T>());
+        }
+    }
+
+    pub fn check_resource<R: Resource>(&mut self, world: &World) {
+        if !world.has_value::<R>() {
+            self.missing.push(type_name::<R>());
+        }
+    }
+
+    /// Merges the report of an item nested by `#[flatten]`.
+    pub fn check_item(&mut self, nested: MatchReport) {
+        self.missing.extend(nested.missing);
+    }
+
+    pub fn is_match(&self) -> bool {
+        !self.is_dead && self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for MatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let item = short_type_name(self.item);
+        if self.is_match() {
+            return write!(f, "{:?} matches `{}`", self.entity, item);
+        }
+
+        write!(f, "{:?} does not match `{}`:", self.entity, item)?;
+        if self.is_dead {
+            write!(f, " the entity is dead;")?;
+        }
+        if !self.missing.is_empty() {
+            let missing: Vec<_> = self.missing.iter().map(|name| short_type_name(name)).collect();
+            write!(f, " missing {};", missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{Builder, VecStorage};
+
+    struct Pos;
+    struct Vel;
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    impl Component for Vel {
+        type Storage = VecStorage<Self>;
+    }
+
+    struct PosChange;
+
+    fn diagnose(world: &World, entity: Entity) -> MatchReport {
+        let mut report = MatchReport::new::<PosChange>(world, entity);
+        report.check_component::<Pos>(world);
+        report.check_component::<Vel>(world);
+        report
+    }
+
+    #[test]
+    fn test_match_report() {
+        let mut world = World::new();
+        world.register::<Pos>();
+        let matching = world.create_entity().with(Pos).build();
+        world.register::<Vel>();
+        world.write_storage().insert(matching, Vel).unwrap();
+        let partial = world.create_entity().with(Pos).build();
+        world.delete_entity(matching).unwrap();
+        let alive = world.create_entity().with(Pos).with(Vel).build();
+
+        assert!(diagnose(&world, alive).is_match());
+        let report = diagnose(&world, partial);
+        assert_eq!(report.missing, vec![type_name::<Vel>()]);
+        assert_eq!(
+            report.to_string(),
+            format!("{:?} does not match `PosChange`: missing Vel;", partial)
+        );
+        let report = diagnose(&world, matching);
+        assert!(report.is_dead);
+        assert!(report.missing.is_empty());
+        assert_eq!(
+            report.to_string(),
+            format!("{:?} does not match `PosChange`: the entity is dead;", matching)
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::{error::Error, fmt};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use specs::{
+    saveload::{SimpleMarker, SimpleMarkerAllocator},
+    Entities, World, WorldExt, Write, WriteStorage,
+};
+
+/// The tag of markers allocated by data items declared with `#[data_item(serialize)]`.
+///
+/// All data items share the marker, so entity references between snapshots of different items are restored.
+pub struct DslSaveload;
+
+pub type SaveMarker = SimpleMarker<DslSaveload>;
+pub type SaveMarkerAllocator = SimpleMarkerAllocator<DslSaveload>;
+
+/// The markers which the generated `save` functions allocate, fetched together with the item storages.
+pub type SaveMarkers<'a> = (
+    Entities<'a>,
+    WriteStorage<'a, SaveMarker>,
+    Write<'a, SaveMarkerAllocator>,
+);
+
+/// Registers the marker storage and allocator, which the generated `save` and `load` functions use.
+pub fn register_save_markers(world: &mut World) {
+    world.register::<SaveMarker>();
+    world.entry::<SaveMarkerAllocator>().or_insert_with(Default::default);
+}
+
+/// Saved entities of a data item, one record per entity:
+///
+/// ```ignore
+/// register_save_markers(&mut world);
+/// let (storages, mut markers) = world.system_data::<(PosChangeData, SaveMarkers)>();
+/// let json = PosChange::save(&storages, &mut markers)?.to_json()?;
+/// PosChange::load(&mut other_world, Snapshot::from_json(&json)?)?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Snapshot<R: Serialize + DeserializeOwned> {
+    pub items: Vec<R>,
+}
+
+impl<R: Serialize + DeserializeOwned> Snapshot<R> {
+    pub fn to_json(&self) -> Result<String, SnapshotError> {
+        serde_json::to_string(self).map_err(SnapshotError::Json)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, SnapshotError> {
+        serde_json::from_str(json).map_err(SnapshotError::Json)
+    }
+
+    /// Encodes the snapshot with `bincode`, which is compact but not self-describing.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotError> {
+        bincode::serialize(self).map_err(SnapshotError::Binary)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        bincode::deserialize(bytes).map_err(SnapshotError::Binary)
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    /// A component failed to convert between its saved data and entity references.
+    Convert(String),
+    Storage(specs::error::Error),
+}
+
+impl SnapshotError {
+    pub fn convert(error: impl fmt::Display) -> Self {
+        SnapshotError::Convert(error.to_string())
+    }
+}
+
+impl From<specs::error::Error> for SnapshotError {
+    fn from(error: specs::error::Error) -> Self {
+        SnapshotError::Storage(error)
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Json(error) => write!(f, "Cannot encode the JSON snapshot: {}", error),
+            SnapshotError::Binary(error) => write!(f, "Cannot encode the binary snapshot: {}", error),
+            SnapshotError::Convert(error) => write!(f, "Cannot convert a saved component: {}", error),
+            SnapshotError::Storage(error) => write!(f, "Cannot insert a loaded component: {}", error),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use specs::{saveload::MarkedBuilder, Builder};
+
+    #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct PosRecord {
+        marker: SaveMarker,
+        position: (f32, f32),
+    }
+
+    #[test]
+    fn test_snapshot_formats() {
+        let mut world = World::new();
+        register_save_markers(&mut world);
+        let entity = world.create_entity().marked::<SaveMarker>().build();
+        let marker = *world.read_storage::<SaveMarker>().get(entity).unwrap();
+        let snapshot = Snapshot {
+            items: vec![PosRecord {
+                marker,
+                position: (1., 2.),
+            }],
+        };
+
+        let json = snapshot.to_json().unwrap();
+        assert_eq!(json, "{\"items\":[{\"marker\":[0],\"position\":[1.0,2.0]}]}");
+        assert_eq!(Snapshot::from_json(&json).unwrap(), snapshot);
+        let bytes = snapshot.to_bytes().unwrap();
+        assert!(bytes.len() < json.len());
+        assert_eq!(Snapshot::from_bytes(&bytes).unwrap(), snapshot);
+        assert!(matches!(
+            Snapshot::<PosRecord>::from_bytes(&bytes[..4]),
+            Err(SnapshotError::Binary(_))
+        ));
+    }
+}
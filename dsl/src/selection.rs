@@ -0,0 +1,95 @@
+use specs::{
+    hibitset::{BitSet, BitSetAnd, BitSetLike},
+    join::Join,
+    world::{EntitiesRes, Index},
+    Entity, ParJoin,
+};
+
+/// Restricts joins of data item views to a selection of entities:
+///
+/// ```ignore
+/// data.view_mut().items_in(&selected).par_join().for_each(|item| { ... });
+/// data.view_mut().items_for(&entities, &selection.0).join().for_each(|item| { ... });
+/// ```
+pub trait JoinSelection: Join + Sized {
+    /// Joins only the entities whose indices are in the mask.
+    fn items_in<B: BitSetLike>(self, mask: B) -> Selected<Self, B> {
+        Selected { join: self, mask }
+    }
+
+    /// Joins only the listed entities. Dead entities are skipped, even if their index is reused.
+    fn items_for(self, entities: &EntitiesRes, selection: &[Entity]) -> Selected<Self, BitSet> {
+        let mut mask = BitSet::new();
+        for entity in selection.iter().filter(|entity| entities.is_alive(**entity)) {
+            mask.add(entity.id());
+        }
+        self.items_in(mask)
+    }
+}
+
+impl<J: Join> JoinSelection for J {}
+
+/// A join restricted to a selection of entities, see `JoinSelection`.
+pub struct Selected<J, B> {
+    join: J,
+    mask: B,
+}
+
+impl<J: Join, B: BitSetLike> Join for Selected<J, B> {
+    type Mask = BitSetAnd<J::Mask, B>;
+    type Type = J::Type;
+    type Value = J::Value;
+
+    unsafe fn open(self) -> (Self::Mask, Self::Value) {
+        let (mask, value) = self.join.open();
+        (BitSetAnd(mask, self.mask), value)
+    }
+
+    unsafe fn get(value: &mut Self::Value, idx: Index) -> Self::Type {
+        J::get(value, idx)
+    }
+}
+
+// SAFETY: The selection only restricts the inner join, which is `ParJoin` itself.
+unsafe impl<J: ParJoin, B: BitSetLike> ParJoin for Selected<J, B> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::iter::ParallelIterator;
+    use specs::{Builder, Component, ReadStorage, VecStorage, World, WorldExt};
+
+    struct Pos(u32);
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    #[test]
+    fn test_selection() {
+        let mut world = World::new();
+        world.register::<Pos>();
+        let entities: Vec<_> = (0..4).map(|i| world.create_entity().with(Pos(i)).build()).collect();
+        world.delete_entity(entities[3]).unwrap();
+        world.maintain();
+        world.create_entity().with(Pos(4)).build();
+
+        let mut mask = BitSet::new();
+        mask.add(entities[0].id());
+        mask.add(entities[2].id());
+        for pos in (&mut world.write_storage::<Pos>()).items_in(&mask).join() {
+            pos.0 += 10;
+        }
+
+        let positions: ReadStorage<Pos> = world.system_data();
+        let selection = [entities[2], entities[3]];
+        let selected: Vec<_> = (&positions)
+            .items_for(&world.entities(), &selection)
+            .join()
+            .map(|pos| pos.0)
+            .collect();
+        assert_eq!(selected, vec![12]);
+        let sum: u32 = (&positions).items_in(&mask).par_join().map(|pos| pos.0).sum();
+        assert_eq!(sum, 22);
+    }
+}
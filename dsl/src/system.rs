@@ -0,0 +1,26 @@
+use specs::{SystemData, World};
+
+use crate::Access;
+
+/// Access metadata of a system declared with `#[system(Data, graph)]`, which adds the system to a `SystemGraph`
+/// and validates its data with `validate` and `try_fetch`.
+///
+/// The metadata is opt-in, since the system data, event channels and hooks must implement `SystemAccess`,
+/// which custom `SystemData` types usually don't.
+pub trait DslSystem {
+    const NAME: &'static str;
+    /// Thread-local systems implement only `RunNow` and must be registered with `with_thread_local`.
+    const THREAD_LOCAL: bool = false;
+
+    fn accesses() -> Vec<Access>;
+
+    /// The accesses whose storages or resources are absent from the world, see `validate`.
+    fn missing(_world: &World) -> Vec<Access> {
+        vec![]
+    }
+}
+
+/// The system data declared in the `#[system]` attribute, which is passed to the `#[run]` method.
+pub trait DslSystemData<'a> {
+    type Data: SystemData<'a>;
+}
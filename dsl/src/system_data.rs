@@ -1,10 +1,38 @@
-use specs::System;
+use specs::{join::Join, SystemData};
+
+use crate::DslSystemData;
 
 pub trait DataItem<'a, 'b> {
     type View;
 }
 
-pub type SystemDataType<'a, S> = <S as System<'a>>::SystemData;
+/// The joined tuple which is converted into the data item, e.g. `(&'a mut Pos, &'a Vel)`.
+/// Items nested by `#[flatten]` join as this tuple within the parent join.
+pub trait ItemTuple {
+    type Tuple;
+}
+
+/// The storages of a data item. It is implemented for the `'static` instance of the item,
+/// so the item can be named without lifetimes, e.g. `SystemTest::assert_item::<PosChange, _>`.
+pub trait ItemStorages<'b> {
+    type Storages: SystemData<'b>;
+
+    /// `true` if the item has change filters, which join only the changes since the previous system run.
+    const TRACKS_CHANGES: bool = false;
+}
+
+/// Joins data items over borrowed item storages.
+///
+/// The defaulted parameter implies `'b: 'a`, so the trait can be required for all lifetimes,
+/// e.g. `for<'a, 'b> ItemView<'a, 'b>`.
+pub trait ItemView<'a, 'b, Bound = &'a &'b ()>: ItemStorages<'b> {
+    type Item: From<<Self::View as Join>::Type>;
+    type View: Join;
+
+    fn view(storages: &'a mut Self::Storages) -> Self::View;
+}
+
+pub type SystemDataType<'a, S> = <S as DslSystemData<'a>>::Data;
 
 pub type DataView<'a, 'b, T> = <T as DataItem<'a, 'b>>::View;
 
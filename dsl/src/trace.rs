@@ -0,0 +1,154 @@
+//! Instrumentation of DSL systems with `tracing`, enabled by the `tracing` feature.
+//!
+//! The macros are expanded by the generated systems, so the feature of this crate decides whether they trace.
+
+/// Enters a span named after the system for the rest of its run.
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __system_span {
+    ($system:expr) => {
+        $crate::tracing::info_span!($system).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __system_span {
+    ($system:expr) => {
+        $crate::NoSpan
+    };
+}
+
+/// Emits a debug event for an entity processed by a generated per-item loop.
+#[cfg(feature = "tracing")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __item_event {
+    ($system:expr, $entity:expr) => {
+        $crate::tracing::debug!(system = $system, entity = ?$entity, "processing item")
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __item_event {
+    ($system:expr, $entity:expr) => {};
+}
+
+/// The span guard of systems without the `tracing` feature.
+#[doc(hidden)]
+pub struct NoSpan;
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use std::{
+        fmt,
+        sync::{Arc, Mutex},
+    };
+
+    use specs::{Builder, Component, Join, VecStorage, World, WorldExt};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Event, Metadata, Subscriber,
+    };
+    use tracing_core::span::Current;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        names: Arc<Mutex<Vec<String>>>,
+        span: Arc<Mutex<Option<&'static Metadata<'static>>>>,
+    }
+
+    #[derive(Default)]
+    struct SystemField(Option<String>);
+
+    impl Visit for SystemField {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "system" {
+                self.0 = Some(value.to_string());
+            }
+        }
+
+        fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            self.names.lock().unwrap().push(span.metadata().name().to_string());
+            *self.span.lock().unwrap() = Some(span.metadata());
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut system = SystemField::default();
+            event.record(&mut system);
+            let level = event.metadata().level().as_str();
+            self.names.lock().unwrap().push(
+                system
+                    .0
+                    .map_or_else(|| level.to_string(), |system| format!("{} {}", level, system)),
+            );
+        }
+
+        fn enter(&self, _span: &Id) {}
+
+        fn exit(&self, _span: &Id) {}
+
+        fn current_span(&self) -> Current {
+            match *self.span.lock().unwrap() {
+                Some(metadata) => Current::new(Id::from_u64(1), metadata),
+                None => Current::none(),
+            }
+        }
+    }
+
+    struct Pos;
+
+    impl Component for Pos {
+        type Storage = VecStorage<Self>;
+    }
+
+    #[test]
+    fn test_system_span() {
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let _span = crate::__system_span!("PhysicsSystem");
+            crate::__item_event!("PhysicsSystem", 0);
+        });
+        assert_eq!(
+            *recorder.names.lock().unwrap(),
+            vec!["PhysicsSystem", "DEBUG PhysicsSystem"]
+        );
+    }
+
+    #[test]
+    fn test_instrumented_join() {
+        let mut world = World::new();
+        world.register::<Pos>();
+        world.create_entity().with(Pos).build();
+        world.create_entity().with(Pos).build();
+
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            let _span = crate::__system_span!("PhysicsSystem");
+            let storage = world.read_storage::<Pos>();
+            assert_eq!(crate::Instrumented::new(&storage).join().count(), 2);
+        });
+        assert_eq!(
+            *recorder.names.lock().unwrap(),
+            vec!["PhysicsSystem", "DEBUG PhysicsSystem", "DEBUG PhysicsSystem"]
+        );
+    }
+}
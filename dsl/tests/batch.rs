@@ -0,0 +1,79 @@
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, DispatcherBuilder, Entity, Join, Read, VecStorage, World, WorldExt},
+    system, Batch, BatchBuilder, DslBatchExt, SystemDataType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(f32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct SubStepCount(usize);
+
+#[data_item]
+#[system_data(MoveData)]
+pub struct Move<'a> {
+    pub entity: Entity,
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+/// Moves the positions by their velocities, inside the batch.
+struct MoveSystem;
+
+#[system(MoveData)]
+impl MoveSystem {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Move = item.into();
+            item.position.0 += item.velocity.0;
+        }
+    }
+}
+
+type SubStepData<'a> = Read<'a, SubStepCount>;
+
+/// Runs the inner systems the configured number of times.
+#[derive(Default)]
+struct SubSteps;
+
+#[system(SubStepData, batch)]
+impl SubSteps {
+    #[run]
+    fn sub_steps(&mut self, count: SystemDataType<Self>) -> usize {
+        count.0
+    }
+}
+
+#[test]
+fn test_batch_of_dsl_systems() {
+    let mut world = World::new();
+    let mut dispatcher = DispatcherBuilder::new()
+        .with_dsl_batch::<SubSteps>(BatchBuilder::new().with(MoveSystem, "move", &[]), "physics", &[])
+        .build();
+    dispatcher.setup(&mut world);
+    world.insert(SubStepCount(3));
+    let entity = world.create_entity().with(Pos(0.)).with(Vel(2.)).build();
+
+    dispatcher.dispatch(&world);
+
+    assert_eq!(world.read_storage::<Pos>().get(entity), Some(&Pos(6.)));
+}
+
+#[test]
+#[should_panic(expected = "register it with `DslBatchExt::with_dsl_batch`")]
+fn test_batch_requires_batch_builder() {
+    DispatcherBuilder::new().with_batch::<Batch<SubSteps>>(DispatcherBuilder::new(), "physics", &[]);
+}
@@ -0,0 +1,103 @@
+use specs_dsl::{
+    data_item,
+    specs::{Component, Entities, Entity, LazyUpdate, Read, RunNow, VecStorage, World, WorldExt, WriteStorage},
+    system, SystemDataType, WorldQuery,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item(bundle = PosChangeBundle)]
+pub struct PosChange<'a> {
+    pub entity: Entity,
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+type SpawnData<'a> = (Entities<'a>, WriteStorage<'a, Pos>, WriteStorage<'a, Vel>);
+
+/// Spawns a moving entity on each run.
+struct Spawner;
+
+#[system(SpawnData)]
+impl Spawner {
+    #[run]
+    fn run(&mut self, (entities, mut positions, mut velocities): SystemDataType<Self>) {
+        let bundle = PosChangeBundle {
+            position: Pos(1),
+            velocity: Vel(1),
+        };
+        bundle
+            .insert_into(entities.create(), &mut positions, &mut velocities)
+            .unwrap();
+    }
+}
+
+type LazySpawnData<'a> = (Entities<'a>, Read<'a, LazyUpdate>);
+
+/// Spawns a moving entity on each run, which is created on the next `World::maintain`.
+struct LazySpawner;
+
+#[system(LazySpawnData)]
+impl LazySpawner {
+    #[run]
+    fn run(&mut self, (entities, lazy): SystemDataType<Self>) {
+        let bundle = PosChangeBundle {
+            position: Pos(2),
+            velocity: Vel(2),
+        };
+        bundle.lazy_spawn(&entities, &lazy);
+    }
+}
+
+fn positions(world: &mut World) -> Vec<(Entity, u32)> {
+    let mut positions = vec![];
+    world.query_mut::<PosChange, _>(|item| positions.push((item.entity, item.position.0)));
+    positions
+}
+
+#[test]
+fn test_spawn_bundle() {
+    let mut world = World::new();
+    let entity = PosChangeBundle {
+        position: Pos(0),
+        velocity: Vel(1),
+    }
+    .spawn(&mut world);
+    assert_eq!(positions(&mut world), vec![(entity, 0)]);
+}
+
+#[test]
+fn test_insert_bundle_from_systems() {
+    let mut world = World::new();
+    let mut spawner = Spawner;
+    let mut lazy_spawner = LazySpawner;
+    spawner.setup(&mut world);
+    lazy_spawner.setup(&mut world);
+
+    spawner.run_now(&world);
+    lazy_spawner.run_now(&world);
+    assert_eq!(
+        positions(&mut world).len(),
+        1,
+        "the lazy bundle is inserted on maintain"
+    );
+
+    world.maintain();
+    let positions: Vec<_> = positions(&mut world)
+        .into_iter()
+        .map(|(_, position)| position)
+        .collect();
+    assert_eq!(positions, vec![1, 2]);
+}
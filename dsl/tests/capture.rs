@@ -0,0 +1,66 @@
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, VecStorage, World, WorldExt},
+    ChangedItem,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(i32, i32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(i32, i32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item(capture)]
+pub struct PosChange<'a> {
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+#[test]
+fn test_capture_diff_of_data_item() {
+    let mut world = World::new();
+    world.register::<Pos>();
+    world.register::<Vel>();
+    let moving = world.create_entity().with(Pos(0, 0)).with(Vel(1, 0)).build();
+    let still = world.create_entity().with(Pos(5, 5)).with(Vel(0, 0)).build();
+
+    let before = PosChange::capture(&world);
+    world.write_storage::<Pos>().get_mut(moving).unwrap().0 += 1;
+    world.write_storage::<Vel>().remove(still);
+    let diff = before.diff(&PosChange::capture(&world));
+
+    assert_eq!(diff.added, vec![]);
+    assert_eq!(
+        diff.removed,
+        vec![(
+            still,
+            PosChangeCapture {
+                position: Pos(5, 5),
+                velocity: Vel(0, 0),
+            }
+        )]
+    );
+    assert_eq!(
+        diff.changed,
+        vec![ChangedItem {
+            entity: moving,
+            fields: vec!["position"],
+            old: PosChangeCapture {
+                position: Pos(0, 0),
+                velocity: Vel(1, 0),
+            },
+            new: PosChangeCapture {
+                position: Pos(1, 0),
+                velocity: Vel(1, 0),
+            },
+        }]
+    );
+}
@@ -0,0 +1,90 @@
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, Entity, FlaggedStorage, Join, RunNow, VecStorage, World, WorldExt},
+    system, ChangeTracker, SystemDataType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+#[data_item]
+#[system_data(SpawnedData)]
+pub struct Spawned<'a> {
+    pub entity: Entity,
+    #[inserted]
+    pub position: &'a Pos,
+}
+
+/// Counts the positions inserted since its previous run.
+#[derive(Default)]
+struct CountSpawned {
+    changes: ChangeTracker,
+    seen: usize,
+}
+
+#[system(SpawnedData, changes = changes)]
+impl CountSpawned {
+    #[run]
+    fn run(&mut self, data: SystemDataType<Self>) {
+        self.seen += data.view().join().count();
+    }
+}
+
+type SpawnedPair<'a> = (SpawnedData<'a>, SpawnedData<'a>);
+
+/// Joins the same change filter twice.
+#[derive(Default)]
+struct CountSpawnedTwice {
+    changes: ChangeTracker,
+    seen: (usize, usize),
+}
+
+#[system(SpawnedPair, changes = changes)]
+impl CountSpawnedTwice {
+    #[run]
+    fn run(&mut self, (first, second): SystemDataType<Self>) {
+        self.seen.0 += first.view().join().count();
+        self.seen.1 += second.view().join().count();
+    }
+}
+
+fn spawn(world: &mut World, count: usize) {
+    for _ in 0..count {
+        world.create_entity().with(Pos(0., 0.)).build();
+    }
+}
+
+#[test]
+fn test_system_instances_track_changes_independently() {
+    let mut world = World::new();
+    let mut first = CountSpawned::default();
+    let mut second = CountSpawned::default();
+    first.setup(&mut world);
+    second.setup(&mut world);
+
+    spawn(&mut world, 2);
+    first.run_now(&world);
+    second.run_now(&world);
+    assert_eq!((first.seen, second.seen), (2, 2));
+
+    spawn(&mut world, 1);
+    first.run_now(&world);
+    first.run_now(&world);
+    second.run_now(&world);
+    assert_eq!((first.seen, second.seen), (3, 3));
+}
+
+#[test]
+fn test_filters_of_one_system_track_changes_independently() {
+    let mut world = World::new();
+    let mut system = CountSpawnedTwice::default();
+    system.setup(&mut world);
+
+    spawn(&mut world, 2);
+    system.run_now(&world);
+    assert_eq!(system.seen, (2, 2));
+}
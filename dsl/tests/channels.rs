@@ -0,0 +1,77 @@
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, Entity, Join, RunNow, VecStorage, World, WorldExt},
+    system, Emitter, Events, SystemDataType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bomb {
+    pub fuse: u32,
+}
+
+impl Component for Bomb {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explosion {
+    pub bomb: Entity,
+}
+
+#[data_item]
+#[system_data(FuseData)]
+pub struct Fuse<'a> {
+    pub entity: Entity,
+    pub bomb: &'a mut Bomb,
+}
+
+/// Burns the fuses, emitting an explosion for each bomb whose fuse burned out.
+struct Detonator;
+
+#[system(FuseData, emits(Explosion))]
+impl Detonator {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>, mut explosions: Emitter<Explosion>) {
+        for item in data.view_mut().join() {
+            let item: Fuse = item.into();
+            if item.bomb.fuse == 0 {
+                explosions.emit(Explosion { bomb: item.entity });
+            } else {
+                item.bomb.fuse -= 1;
+            }
+        }
+    }
+}
+
+/// Records the exploded bombs.
+#[derive(Default)]
+struct Shrapnel {
+    exploded: Vec<Entity>,
+}
+
+#[system(FuseData, reads(Explosion))]
+impl Shrapnel {
+    #[run]
+    fn run(&mut self, _data: SystemDataType<Self>, explosions: Events<Explosion>) {
+        self.exploded.extend(explosions.map(|explosion| explosion.bomb));
+    }
+}
+
+#[test]
+fn test_event_channels() {
+    let mut world = World::new();
+    let mut detonator = Detonator;
+    let mut shrapnel = Shrapnel::default();
+    detonator.setup(&mut world);
+    shrapnel.setup(&mut world);
+    let short = world.create_entity().with(Bomb { fuse: 0 }).build();
+    let long = world.create_entity().with(Bomb { fuse: 1 }).build();
+
+    detonator.run_now(&world);
+    shrapnel.run_now(&world);
+    assert_eq!(shrapnel.exploded, vec![short]);
+
+    detonator.run_now(&world);
+    shrapnel.run_now(&world);
+    assert_eq!(shrapnel.exploded, vec![short, short, long]);
+}
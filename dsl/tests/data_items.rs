@@ -0,0 +1,226 @@
+use specs_dsl::{
+    data_item,
+    specs::{Component, Join, VecStorage},
+    system, SystemDataType, SystemTest,
+};
+
+use self::items::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(f32, f32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+struct DeltaTime(f32);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rot(f32);
+
+impl Component for Rot {
+    type Storage = VecStorage<Self>;
+}
+
+/// The main view traits of all items are in scope where the systems are declared.
+mod items {
+    use super::{Pos, Rot, Vel};
+    use specs_dsl::data_item;
+
+    #[data_item]
+    pub struct Transform<'a> {
+        pub position: &'a mut Pos,
+        pub rotation: &'a Rot,
+    }
+
+    #[data_item]
+    #[system_data(MoveData)]
+    pub struct Move<'a> {
+        #[flatten]
+        pub transform: Transform<'a>,
+        pub velocity: &'a Vel,
+    }
+
+    #[data_item]
+    #[system_data(TurnData)]
+    pub struct Turn<'a> {
+        pub rotation: &'a mut Rot,
+        pub velocity: &'a Vel,
+    }
+}
+
+#[data_item]
+struct PosChange<'a> {
+    position: &'a mut Pos,
+    velocity: &'a Vel,
+}
+
+/// Resources cannot be joined, so the item has no item view and is consumed only through its system data.
+#[data_item]
+#[system_data(TimedPosChangeData)]
+struct TimedPosChange<'a> {
+    position: &'a mut Pos,
+    velocity: &'a Vel,
+    #[resource]
+    delta: &'a DeltaTime,
+}
+
+struct TimedPhysics;
+
+#[system(TimedPosChangeData)]
+impl TimedPhysics {
+    #[run]
+    fn run(&mut self, (mut positions, velocities, delta): SystemDataType<Self>) {
+        for (position, velocity) in (&mut positions, &velocities).join() {
+            let item = TimedPosChange {
+                position,
+                velocity,
+                delta: &delta,
+            };
+            item.position.0 += item.velocity.0 * item.delta.0;
+            item.position.1 += item.velocity.1 * item.delta.0;
+        }
+    }
+}
+
+#[test]
+fn test_data_item_with_resource_field() {
+    SystemTest::new(TimedPhysics)
+        .with_resource(DeltaTime(0.5))
+        .spawn((Pos(0., 0.), Vel(1., 2.)))
+        .run(2)
+        .assert_item::<PosChange, _>(0, |item| {
+            assert_eq!(*item.position, Pos(1., 2.));
+            assert_eq!(*item.velocity, Vel(1., 2.));
+        });
+}
+
+struct Movement;
+
+#[system(MoveData)]
+impl Movement {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Move = item.into();
+            item.transform.position.0 += item.velocity.0 * item.transform.rotation.0;
+        }
+    }
+}
+
+struct Steering;
+
+#[system(TurnData)]
+impl Steering {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Turn = item.into();
+            item.rotation.0 += item.velocity.1;
+        }
+    }
+}
+
+#[test]
+fn test_flattened_item_views_in_scope() {
+    SystemTest::new(Movement)
+        .spawn((Pos(0., 0.), Rot(2.), Vel(1., 0.)))
+        .run(1)
+        .assert_item::<Transform, _>(0, |item| assert_eq!(*item.position, Pos(2., 0.)));
+    SystemTest::new(Steering)
+        .spawn((Rot(0.), Vel(0., 1.)))
+        .run(1)
+        .assert_item::<Turn, _>(0, |item| assert_eq!(*item.rotation, Rot(1.)));
+}
+
+macro_rules! counters {
+    ($($name:ident),*) => {
+        $(
+            #[derive(Clone, Debug, PartialEq)]
+            pub struct $name(u32);
+
+            impl Component for $name {
+                type Storage = VecStorage<Self>;
+            }
+        )*
+    };
+}
+
+counters!(C0, C1, C2, C3, C4, C5, C6, C7, C8, C9, C10, C11, C12, C13, C14, C15, C16, C17);
+
+/// More fields than specs joins in a single tuple, so the item storages are nested.
+#[data_item(bundle = WideBundle)]
+#[system_data(WideData)]
+struct Wide<'a> {
+    c0: &'a mut C0,
+    c1: &'a C1,
+    c2: &'a C2,
+    c3: &'a C3,
+    c4: &'a C4,
+    c5: &'a C5,
+    c6: &'a C6,
+    c7: &'a C7,
+    c8: &'a C8,
+    c9: &'a C9,
+    c10: &'a C10,
+    c11: &'a C11,
+    c12: &'a C12,
+    c13: &'a C13,
+    c14: &'a C14,
+    c15: &'a C15,
+    c16: &'a C16,
+    c17: &'a mut C17,
+}
+
+struct WideSum;
+
+#[system(WideData)]
+impl WideSum {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Wide = item.into();
+            item.c0.0 = item.c1.0 + item.c2.0 + item.c3.0 + item.c4.0 + item.c5.0 + item.c6.0 + item.c7.0 + item.c8.0;
+            item.c17.0 =
+                item.c9.0 + item.c10.0 + item.c11.0 + item.c12.0 + item.c13.0 + item.c14.0 + item.c15.0 + item.c16.0;
+        }
+    }
+}
+
+#[test]
+fn test_data_item_beyond_join_tuple_limit() {
+    let bundle = |n| WideBundle {
+        c0: C0(0),
+        c1: C1(n),
+        c2: C2(n),
+        c3: C3(n),
+        c4: C4(n),
+        c5: C5(n),
+        c6: C6(n),
+        c7: C7(n),
+        c8: C8(n),
+        c9: C9(2 * n),
+        c10: C10(2 * n),
+        c11: C11(2 * n),
+        c12: C12(2 * n),
+        c13: C13(2 * n),
+        c14: C14(2 * n),
+        c15: C15(2 * n),
+        c16: C16(2 * n),
+        c17: C17(0),
+    };
+    SystemTest::new(WideSum)
+        .spawn(bundle(1))
+        .spawn(bundle(3))
+        .run(1)
+        .assert_item::<Wide, _>(0, |item| assert_eq!((item.c0.0, item.c17.0), (8, 16)))
+        .assert_item::<Wide, _>(1, |item| assert_eq!((item.c0.0, item.c17.0), (24, 48)));
+}
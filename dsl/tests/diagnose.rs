@@ -0,0 +1,131 @@
+use specs_dsl::{
+    specs::{Component, Join, RunNow, VecStorage, WorldExt},
+    system, SystemDataType, SystemTest,
+};
+
+use self::items::*;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rot(u32);
+
+impl Component for Rot {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct DeltaTime(u32);
+
+mod items {
+    use super::{DeltaTime, Pos, Rot, Vel};
+    use specs_dsl::data_item;
+
+    #[data_item]
+    pub struct Transform<'a> {
+        pub position: &'a mut Pos,
+        pub rotation: &'a Rot,
+    }
+
+    #[data_item]
+    #[system_data(MoveData)]
+    pub struct Move<'a> {
+        #[flatten]
+        pub transform: Transform<'a>,
+        pub velocity: &'a Vel,
+    }
+
+    #[data_item]
+    #[system_data(TimedMoveData)]
+    pub struct TimedMove<'a> {
+        pub position: &'a mut Pos,
+        pub velocity: &'a Vel,
+        #[resource]
+        pub delta: &'a DeltaTime,
+    }
+}
+
+struct Movement;
+
+#[system(MoveData)]
+impl Movement {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Move = item.into();
+            item.transform.position.0 += item.velocity.0 * item.transform.rotation.0;
+        }
+    }
+}
+
+struct TimedMovement;
+
+#[system(TimedMoveData)]
+impl TimedMovement {
+    #[run]
+    fn run(&mut self, (mut positions, velocities, delta): SystemDataType<Self>) {
+        for (position, velocity) in (&mut positions, &velocities).join() {
+            let item = TimedMove {
+                position,
+                velocity,
+                delta: &delta,
+            };
+            item.position.0 += item.velocity.0 * item.delta.0;
+        }
+    }
+}
+
+#[test]
+fn test_diagnose_unmoved_entities() {
+    let mut test = SystemTest::new(Movement)
+        .spawn((Pos(0), Rot(1), Vel(1)))
+        .spawn((Pos(0), Vel(1)))
+        .spawn((Pos(0), Rot(1), Vel(1)))
+        .run(1)
+        .assert_item::<Move, _>(0, |item| assert_eq!(*item.transform.position, Pos(1)))
+        .assert_no_item::<Move>(1);
+    let (moved, unrotated, deleted) = (test.entity(0), test.entity(1), test.entity(2));
+    test.world_mut().delete_entity(deleted).unwrap();
+    let world = test.world();
+
+    assert!(Move::diagnose(world, moved).is_match());
+    let report = Move::diagnose(world, unrotated);
+    assert_eq!(report.missing, vec![std::any::type_name::<Rot>()]);
+    assert_eq!(
+        report.to_string(),
+        format!("{:?} does not match `Move<'_>`: missing Rot;", unrotated)
+    );
+    let report = Move::diagnose(world, deleted);
+    assert!(report.is_dead);
+    assert!(!report.is_match());
+}
+
+#[test]
+fn test_diagnose_missing_resource() {
+    let mut test = SystemTest::new(Movement).spawn((Pos(0), Rot(1), Vel(1)));
+    let entity = test.entity(0);
+    let report = TimedMove::diagnose(test.world(), entity);
+    assert_eq!(
+        report.to_string(),
+        format!("{:?} does not match `TimedMove<'_>`: missing DeltaTime;", entity)
+    );
+
+    test.world_mut().insert(DeltaTime(2));
+    assert!(TimedMove::diagnose(test.world(), entity).is_match());
+    let mut timed_movement = TimedMovement;
+    timed_movement.setup(test.world_mut());
+    timed_movement.run_now(test.world());
+    test.assert_item::<Transform, _>(0, |item| assert_eq!(*item.position, Pos(2)));
+}
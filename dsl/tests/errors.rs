@@ -0,0 +1,131 @@
+use std::fmt;
+
+use specs_dsl::{
+    data_item,
+    specs::{Component, Join, VecStorage},
+    system, SystemDataType, SystemErrors, SystemTest,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(i32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(i32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item]
+#[system_data(PosChangeData)]
+pub struct PosChange<'a> {
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+#[derive(Debug)]
+pub struct BelowGround(i32);
+
+impl fmt::Display for BelowGround {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the position {} is below the ground", self.0)
+    }
+}
+
+/// Moves the entities, failing for those which fell below the ground.
+fn apply_velocities(data: &mut PosChangeData) -> Result<(), BelowGround> {
+    for item in data.view_mut().join() {
+        let item: PosChange = item.into();
+        item.position.0 += item.velocity.0;
+        if item.position.0 < 0 {
+            return Err(BelowGround(item.position.0));
+        }
+    }
+    Ok(())
+}
+
+struct Physics;
+
+#[system(PosChangeData)]
+impl Physics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) -> Result<(), BelowGround> {
+        apply_velocities(&mut data)
+    }
+}
+
+struct LoggedPhysics;
+
+#[system(PosChangeData, on_error = "log")]
+impl LoggedPhysics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) -> Result<(), BelowGround> {
+        apply_velocities(&mut data)
+    }
+}
+
+struct StrictPhysics;
+
+#[system(PosChangeData, on_error = "panic")]
+impl StrictPhysics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) -> Result<(), BelowGround> {
+        apply_velocities(&mut data)
+    }
+}
+
+struct InfalliblePhysics;
+
+#[system(PosChangeData)]
+impl InfalliblePhysics {
+    // The explicit unit return type is the case under test.
+    #[allow(clippy::unused_unit)]
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) -> () {
+        apply_velocities(&mut data).ok();
+    }
+}
+
+#[test]
+fn test_collect_errors() {
+    let mut test = SystemTest::new(Physics)
+        .spawn((Pos(1), Vel(-1)))
+        .run(1)
+        .assert_resource::<SystemErrors, _>(|errors| assert!(errors.is_empty()))
+        .run(2);
+    let errors = test.world_mut().fetch_mut::<SystemErrors>().take();
+    let messages: Vec<_> = errors.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "The system `Physics` failed: the position -1 is below the ground",
+            "The system `Physics` failed: the position -2 is below the ground",
+        ]
+    );
+}
+
+#[test]
+fn test_log_errors() {
+    SystemTest::new(LoggedPhysics)
+        .spawn((Pos(0), Vel(-1)))
+        .run(1)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(-1)));
+}
+
+#[test]
+#[should_panic(expected = "The system `StrictPhysics` failed: the position -1 is below the ground")]
+fn test_panic_on_error() {
+    SystemTest::new(StrictPhysics).spawn((Pos(0), Vel(-1))).run(1);
+}
+
+#[test]
+fn test_unit_return() {
+    SystemTest::new(InfalliblePhysics)
+        .spawn((Pos(0), Vel(-1)))
+        .run(1)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(-1)));
+}
@@ -0,0 +1,85 @@
+use specs_dsl::{
+    data_item,
+    specs::{
+        shred::ResourceId, Builder, Component, Entities, Entity, Join, LazyUpdate, Read, ReadExpect, RunNow,
+        SystemData, VecStorage, World, WorldExt,
+    },
+    system, SystemDataType, SystemGraph,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+pub struct Gravity(f32);
+
+#[data_item]
+#[system_data(FallData)]
+pub struct Fall<'a> {
+    pub entity: Entity,
+    pub position: &'a mut Pos,
+}
+
+/// Moves the positions down, declared for the system graph.
+struct Falling;
+
+#[system(FallData, graph)]
+impl Falling {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Fall = item.into();
+            item.position.1 -= 1.;
+        }
+    }
+}
+
+/// System data that has no access metadata.
+#[derive(SystemData)]
+pub struct SpawnData<'a> {
+    entities: Entities<'a>,
+    gravity: ReadExpect<'a, Gravity>,
+    lazy: Read<'a, LazyUpdate>,
+}
+
+/// Spawns one position per run at the gravity height.
+struct Spawner;
+
+#[system(SpawnData)]
+impl Spawner {
+    #[run]
+    fn run(&mut self, data: SystemDataType<Self>) {
+        let entity = data.entities.create();
+        data.lazy.insert(entity, Pos(0., data.gravity.0));
+    }
+}
+
+#[test]
+fn test_system_over_custom_system_data() {
+    let mut world = World::new();
+    world.insert(Gravity(10.));
+    Spawner.setup(&mut world);
+    Falling.setup(&mut world);
+
+    Spawner.run_now(&world);
+    world.maintain();
+    Falling.run_now(&world);
+
+    let positions = world.read_storage::<Pos>();
+    assert_eq!(positions.join().cloned().collect::<Vec<_>>(), vec![Pos(0., 9.)]);
+}
+
+#[test]
+fn test_graph_system() {
+    let graph = SystemGraph::new().with::<Falling>("falling", &[]);
+
+    let mut world = World::new();
+    world.create_entity().build();
+    assert!(graph.validate(&world).is_err());
+    Falling.setup(&mut world);
+    assert!(graph.validate(&world).is_ok());
+    assert_eq!(graph.components().len(), 1);
+}
@@ -0,0 +1,59 @@
+use specs_dsl::{
+    data_item,
+    specs::{Component, Join, ReadStorage, System, VecStorage, WriteStorage},
+    SystemTest,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item]
+struct PosChange<'a> {
+    position: &'a mut Pos,
+    velocity: &'a Vel,
+}
+
+/// A plain specs system, the harness runs any system.
+struct Physics;
+
+impl<'a> System<'a> for Physics {
+    type SystemData = (WriteStorage<'a, Pos>, ReadStorage<'a, Vel>);
+
+    fn run(&mut self, (mut positions, velocities): Self::SystemData) {
+        for (position, velocity) in (&mut positions, &velocities).join() {
+            position.0 += velocity.0;
+        }
+    }
+}
+
+#[test]
+fn test_system_test() {
+    SystemTest::new(Physics)
+        .spawn((Pos(0), Vel(2)))
+        .spawn((Pos(1),))
+        .run(3)
+        .assert_item::<PosChange, _>(0, |item| {
+            assert_eq!(*item.position, Pos(6));
+            assert_eq!(*item.velocity, Vel(2));
+        })
+        .assert_no_item::<PosChange>(1);
+}
+
+#[test]
+#[should_panic(expected = "does not match the data item")]
+fn test_missing_item() {
+    SystemTest::new(Physics)
+        .spawn((Pos(1),))
+        .assert_item::<PosChange, _>(0, |_| {});
+}
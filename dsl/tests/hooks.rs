@@ -0,0 +1,55 @@
+use specs_dsl::{
+    specs::{Builder, Component, Entities, Entity, FlaggedStorage, RunNow, VecStorage, World, WorldExt},
+    system, ChangeTracker, SystemDataType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bomb;
+
+impl Component for Bomb {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+/// Records the entities of the bomb hooks.
+#[derive(Default)]
+struct Bombs {
+    changes: ChangeTracker,
+    armed: Vec<Entity>,
+    exploded: Vec<Entity>,
+}
+
+#[system(Entities, changes = changes)]
+impl Bombs {
+    #[on_insert(Bomb)]
+    fn arm(&mut self, entity: Entity, _entities: &mut SystemDataType<Self>) {
+        self.armed.push(entity);
+    }
+
+    #[on_remove(Bomb)]
+    fn explode(&mut self, entity: Entity, _entities: &mut SystemDataType<Self>) {
+        self.exploded.push(entity);
+    }
+}
+
+#[test]
+fn test_removal_hook_of_deleted_then_recreated_entity() {
+    let mut world = World::new();
+    let mut bombs = Bombs::default();
+    bombs.setup(&mut world);
+    let deleted = world.create_entity().with(Bomb).build();
+    bombs.run_now(&world);
+    assert_eq!(bombs.armed, vec![deleted]);
+
+    world.delete_entity(deleted).unwrap();
+    world.maintain();
+    let recreated = world.create_entity().build();
+    assert_eq!(recreated.id(), deleted.id(), "the index is reused");
+    bombs.run_now(&world);
+    assert_eq!(bombs.exploded, vec![deleted]);
+
+    let disarmed = world.create_entity().with(Bomb).build();
+    world.write_storage::<Bomb>().remove(disarmed);
+    bombs.run_now(&world);
+    assert_eq!(bombs.armed, vec![deleted], "the bomb was removed before the run");
+    assert_eq!(bombs.exploded, vec![deleted, disarmed]);
+}
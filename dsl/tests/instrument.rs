@@ -0,0 +1,87 @@
+#![cfg(feature = "instrument")]
+
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, Entity, Join, RunNow, VecStorage, World, WorldExt},
+    system, SystemDataType, SystemMetrics, SystemPanics,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item]
+#[system_data(MoveData)]
+pub struct Move<'a> {
+    pub entity: Entity,
+    pub position: &'a mut Pos,
+}
+
+/// Moves the positions through the view of the data item.
+struct MovePositions;
+
+#[system(MoveData, metrics)]
+impl MovePositions {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Move = item.into();
+            item.position.0 += 1.;
+        }
+    }
+}
+
+#[test]
+fn test_view_items_are_counted() {
+    let mut world = World::new();
+    MovePositions.setup(&mut world);
+    for _ in 0..3 {
+        world.create_entity().with(Pos(0., 0.)).build();
+    }
+
+    MovePositions.run_now(&world);
+    MovePositions.run_now(&world);
+
+    let stats = world.fetch::<SystemMetrics>().stats("MovePositions").unwrap();
+    assert_eq!(stats.items, 6);
+    assert_eq!(stats.samples.back().unwrap().items, 3);
+}
+
+/// Panics at the position of the given x, or after the join without it.
+struct PanicAt(f32);
+
+#[system(MoveData, catch_panics)]
+impl PanicAt {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        for item in data.view_mut().join() {
+            let item: Move = item.into();
+            if item.position.0 == self.0 {
+                panic!("cannot move");
+            }
+        }
+        panic!("moved");
+    }
+}
+
+#[test]
+fn test_panics_are_reported_with_the_joined_entity() {
+    let mut world = World::new();
+    PanicAt(0.).setup(&mut world);
+    let entities: Vec<_> = (0..3)
+        .map(|x| world.create_entity().with(Pos(x as f32, 0.)).build())
+        .collect();
+
+    PanicAt(1.).run_now(&world);
+    PanicAt(5.).run_now(&world);
+
+    let panics = world.fetch_mut::<SystemPanics>().take();
+    assert_eq!(panics.len(), 2);
+    assert_eq!(panics[0].entity, Some(entities[1]));
+    assert_eq!(panics[0].message, "cannot move");
+    assert_eq!(panics[1].entity, None, "the entity is not reported after the join");
+    assert_eq!(panics[1].message, "moved");
+}
@@ -0,0 +1,62 @@
+use std::{
+    sync::{Arc, Barrier},
+    thread,
+};
+
+use specs_dsl::{
+    specs::{Builder, Component, Join, ReadExpect, ReadStorage, RunNow, VecStorage, World, WorldExt},
+    system, JoinCount, SystemDataType, SystemMetrics,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+type CountData<'a> = (ReadStorage<'a, Pos>, ReadExpect<'a, Arc<Barrier>>);
+
+/// Counts the positions, then waits for the other worlds to count theirs.
+struct CountPositions;
+
+#[system(CountData, metrics)]
+impl CountPositions {
+    #[run]
+    fn run(&mut self, (positions, barrier): SystemDataType<Self>) {
+        (&positions).counted().join().for_each(drop);
+        barrier.wait();
+    }
+}
+
+fn world_with_positions(count: usize, barrier: &Arc<Barrier>) -> World {
+    let mut world = World::new();
+    world.insert(barrier.clone());
+    CountPositions.setup(&mut world);
+    for _ in 0..count {
+        world.create_entity().with(Pos(0., 0.)).build();
+    }
+    world
+}
+
+fn items(world: &World) -> u64 {
+    world.fetch::<SystemMetrics>().stats("CountPositions").unwrap().items
+}
+
+#[test]
+fn test_worlds_count_items_independently() {
+    let barrier = Arc::new(Barrier::new(2));
+    let worlds: Vec<_> = vec![world_with_positions(2, &barrier), world_with_positions(3, &barrier)]
+        .into_iter()
+        .map(|world| {
+            thread::spawn(move || {
+                CountPositions.run_now(&world);
+                world
+            })
+        })
+        .collect();
+    let worlds: Vec<_> = worlds.into_iter().map(|world| world.join().unwrap()).collect();
+
+    assert_eq!(items(&worlds[0]), 2);
+    assert_eq!(items(&worlds[1]), 3);
+}
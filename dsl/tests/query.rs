@@ -0,0 +1,79 @@
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, Entity, FlaggedStorage, VecStorage, World, WorldExt},
+    WorldQuery,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Heading(u32);
+
+impl Component for Heading {
+    type Storage = FlaggedStorage<Self, VecStorage<Self>>;
+}
+
+#[data_item]
+struct PosChange<'a> {
+    position: &'a mut Pos,
+    velocity: &'a Vel,
+}
+
+#[data_item]
+struct Rendered<'a> {
+    entity: Entity,
+    position: &'a Pos,
+}
+
+#[data_item]
+pub struct Turned<'a> {
+    pub entity: Entity,
+    #[modified]
+    pub heading: &'a Heading,
+}
+
+fn world() -> World {
+    let mut world = World::new();
+    world.register::<Pos>();
+    world.register::<Vel>();
+    world.create_entity().with(Pos(0)).with(Vel(2)).build();
+    world.create_entity().with(Pos(1)).build();
+    world
+}
+
+#[test]
+fn test_query() {
+    let mut world = world();
+    world.query_mut::<PosChange, _>(|item| item.position.0 += item.velocity.0);
+
+    let mut positions = vec![];
+    world.query::<Rendered, _>(|item| positions.push((item.entity.id(), item.position.0)));
+    assert_eq!(positions, vec![(0, 2), (1, 1)]);
+}
+
+#[test]
+#[should_panic(expected = "can be queried only by `query_mut`")]
+fn test_query_writing_item() {
+    world().query::<PosChange, _>(|_| {});
+}
+
+#[test]
+#[should_panic(expected = "has change filters, it can be joined only by systems")]
+fn test_query_change_filtered_item() {
+    let mut world = world();
+    world.register::<Heading>();
+    world.create_entity().with(Heading(0)).build();
+    world.query::<Turned, _>(|item| assert_eq!(item.heading.0, 0));
+}
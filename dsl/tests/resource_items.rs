@@ -0,0 +1,51 @@
+use specs_dsl::{
+    resource_item,
+    specs::{RunNow, World, WorldExt},
+    system, SystemDataType, SystemTest,
+};
+
+#[derive(Default)]
+pub struct DeltaTime(u32);
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Score(u32);
+
+#[resource_item]
+#[system_data(FrameData)]
+pub struct Frame<'a> {
+    pub dt: &'a DeltaTime,
+    pub score: &'a mut Score,
+}
+
+/// Scores the time survived, without joining any entities.
+struct Survival;
+
+#[system(FrameData)]
+impl Survival {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        let frame = Frame::from(&mut data);
+        frame.score.0 += frame.dt.0;
+    }
+}
+
+#[test]
+fn test_resource_item() {
+    SystemTest::new(Survival)
+        .with_resource(DeltaTime(2))
+        .run(3)
+        .assert_resource::<Score, _>(|score| assert_eq!(*score, Score(6)));
+}
+
+#[test]
+fn test_resource_item_setup() {
+    let mut world = World::new();
+    let mut survival = Survival;
+    survival.setup(&mut world);
+    survival.run_now(&world);
+    assert_eq!(
+        *world.fetch::<Score>(),
+        Score(0),
+        "the default resources are inserted on setup"
+    );
+}
@@ -0,0 +1,88 @@
+use specs_dsl::{
+    data_item,
+    specs::{Component, Join, Read, VecStorage},
+    system, SystemDataType, SystemTest,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Default)]
+pub struct Paused(bool);
+
+#[data_item]
+#[system_data(PosChangeData)]
+pub struct PosChange<'a> {
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+fn apply_velocities(data: &mut PosChangeData) {
+    for item in data.view_mut().join() {
+        let item: PosChange = item.into();
+        item.position.0 += item.velocity.0;
+    }
+}
+
+struct SlowPhysics;
+
+#[system(PosChangeData, every = 3)]
+impl SlowPhysics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        apply_velocities(&mut data);
+    }
+}
+
+type PausableData<'a> = (PosChangeData<'a>, Read<'a, Paused>);
+
+fn is_running((_, paused): &PausableData) -> bool {
+    !paused.0
+}
+
+struct PausablePhysics;
+
+#[system(PausableData, run_if = is_running)]
+impl PausablePhysics {
+    #[run]
+    fn run(&mut self, (mut data, _): SystemDataType<Self>) {
+        apply_velocities(&mut data);
+    }
+}
+
+#[test]
+fn test_every() {
+    SystemTest::new(SlowPhysics)
+        .spawn((Pos(0), Vel(1)))
+        .run(1)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(1)))
+        .run(2)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(1)))
+        .run(1)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(2)))
+        .run(3)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(3)));
+}
+
+#[test]
+fn test_run_if() {
+    SystemTest::new(PausablePhysics)
+        .with_resource(Paused(true))
+        .spawn((Pos(0), Vel(1)))
+        .run(2)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(0)))
+        .with_resource(Paused(false))
+        .run(2)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(2)));
+}
@@ -0,0 +1,85 @@
+use specs_dsl::{
+    data_item,
+    specs::{Component, Join, RunningTime, System, VecStorage},
+    system, SystemDataType, SystemTest,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item]
+#[system_data(PosChangeData)]
+pub struct PosChange<'a> {
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+fn apply_velocities(data: &mut PosChangeData) {
+    for item in data.view_mut().join() {
+        let item: PosChange = item.into();
+        item.position.0 += item.velocity.0;
+    }
+}
+
+struct HeavyPhysics;
+
+#[system(PosChangeData, cost = "very_long")]
+impl HeavyPhysics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        apply_velocities(&mut data);
+    }
+}
+
+/// Estimates its running time from the number of entities seen by the previous run.
+#[derive(Default)]
+struct AdaptivePhysics {
+    entities: usize,
+}
+
+#[system(PosChangeData)]
+impl AdaptivePhysics {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        self.entities = data.view().join().count();
+        apply_velocities(&mut data);
+    }
+
+    #[running_time]
+    fn estimate(&self) -> RunningTime {
+        if self.entities > 1 {
+            RunningTime::Long
+        } else {
+            RunningTime::Short
+        }
+    }
+}
+
+#[test]
+fn test_system_cost() {
+    let test = SystemTest::new(HeavyPhysics)
+        .spawn((Pos(0), Vel(2)))
+        .run(1)
+        .assert_item::<PosChange, _>(0, |item| assert_eq!(*item.position, Pos(2)));
+    assert_eq!(System::running_time(test.system()) as u8, RunningTime::VeryLong as u8);
+}
+
+#[test]
+fn test_running_time_method() {
+    let test = SystemTest::new(AdaptivePhysics::default()).spawn((Pos(0), Vel(2)));
+    assert_eq!(System::running_time(test.system()) as u8, RunningTime::Short as u8);
+
+    let test = test.spawn((Pos(1), Vel(1))).run(1);
+    assert_eq!(System::running_time(test.system()) as u8, RunningTime::Long as u8);
+}
@@ -0,0 +1,76 @@
+#![cfg(feature = "serialize")]
+
+use serde::{Deserialize, Serialize};
+use specs::{
+    error::NoError,
+    saveload::{ConvertSaveload, Marker},
+    Builder, Component, Entities, Entity, Join, ReadStorage, VecStorage, World, WorldExt,
+};
+use specs_dsl::{data_item, register_save_markers, SaveMarkers, Snapshot};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pos(f32, f32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq, specs::ConvertSaveload)]
+pub struct Target(Entity);
+
+impl Component for Target {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item(serialize)]
+#[system_data(ChaseData)]
+pub struct Chase<'a> {
+    pub position: &'a Pos,
+    pub target: &'a Target,
+}
+
+fn save(world: &mut World) -> Snapshot<ChaseRecord> {
+    register_save_markers(world);
+    let (storages, mut markers) = world.system_data::<(ChaseData, SaveMarkers)>();
+    Chase::save(&storages, &mut markers).unwrap()
+}
+
+fn targets(world: &World) -> Vec<(Entity, Pos, Entity)> {
+    let (entities, positions, targets) = world.system_data::<(Entities, ReadStorage<Pos>, ReadStorage<Target>)>();
+    (&entities, &positions, &targets)
+        .join()
+        .map(|(entity, position, target)| (entity, position.clone(), target.0))
+        .collect()
+}
+
+#[test]
+fn test_save_and_load_entity_references() {
+    let mut world = World::new();
+    world.register::<Pos>();
+    world.register::<Target>();
+    let leader = world.create_entity().build();
+    let follower = world.create_entity().with(Pos(1., 2.)).with(Target(leader)).build();
+    world.write_storage().insert(leader, Pos(3., 4.)).unwrap();
+    world.write_storage().insert(leader, Target(follower)).unwrap();
+
+    let json = save(&mut world).to_json().unwrap();
+    let bytes = save(&mut world).to_bytes().unwrap();
+    assert_eq!(
+        save(&mut world).to_json().unwrap(),
+        json,
+        "the markers are reused by later saves"
+    );
+
+    let mut loaded_world = World::new();
+    loaded_world.create_entity().build();
+    let loaded = Chase::load(&mut loaded_world, Snapshot::from_json(&json).unwrap()).unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert!(loaded.iter().all(|entity| entity.id() != 0));
+    let mut expected = vec![(loaded[0], Pos(3., 4.), loaded[1]), (loaded[1], Pos(1., 2.), loaded[0])];
+    expected.sort_by_key(|(entity, _, _)| entity.id());
+    assert_eq!(targets(&loaded_world), expected);
+
+    let reloaded = Chase::load(&mut loaded_world, Snapshot::from_bytes(&bytes).unwrap()).unwrap();
+    assert_eq!(reloaded, loaded, "entities with known markers are reused");
+    assert_eq!(loaded_world.entities().join().count(), 3);
+}
@@ -0,0 +1,78 @@
+use std::{cell::RefCell, rc::Rc};
+
+use specs_dsl::{
+    data_item,
+    specs::{Builder, Component, DispatcherBuilder, Join, RunNow, VecStorage, World, WorldExt},
+    system, SystemDataType,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pos(u32);
+
+impl Component for Pos {
+    type Storage = VecStorage<Self>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Vel(u32);
+
+impl Component for Vel {
+    type Storage = VecStorage<Self>;
+}
+
+#[data_item]
+#[system_data(PosChangeData)]
+pub struct PosChange<'a> {
+    pub position: &'a mut Pos,
+    pub velocity: &'a Vel,
+}
+
+/// Moves the entities and draws their positions into a shared buffer, which is not `Send`.
+struct Renderer {
+    frames: Rc<RefCell<Vec<Vec<u32>>>>,
+}
+
+#[system(PosChangeData, thread_local)]
+impl Renderer {
+    #[run]
+    fn run(&mut self, mut data: SystemDataType<Self>) {
+        let frame = data
+            .view_mut()
+            .join()
+            .map(|item| {
+                let item: PosChange = item.into();
+                item.position.0 += item.velocity.0;
+                item.position.0
+            })
+            .collect();
+        self.frames.borrow_mut().push(frame);
+    }
+}
+
+#[test]
+fn test_thread_local_system_in_dispatcher() {
+    let frames = Rc::new(RefCell::new(vec![]));
+    let mut world = World::new();
+    let mut dispatcher = DispatcherBuilder::new()
+        .with_thread_local(Renderer { frames: frames.clone() })
+        .build();
+    dispatcher.setup(&mut world);
+    world.create_entity().with(Pos(1)).with(Vel(2)).build();
+    world.create_entity().with(Pos(3)).build();
+
+    dispatcher.dispatch(&world);
+    dispatcher.dispatch(&world);
+    assert_eq!(*frames.borrow(), vec![vec![3], vec![5]]);
+}
+
+#[test]
+fn test_thread_local_system_run_now() {
+    let frames = Rc::new(RefCell::new(vec![]));
+    let mut world = World::new();
+    let mut renderer = Renderer { frames: frames.clone() };
+    renderer.setup(&mut world);
+    world.create_entity().with(Pos(1)).with(Vel(2)).build();
+
+    renderer.run_now(&world);
+    assert_eq!(*frames.borrow(), vec![vec![3]]);
+}